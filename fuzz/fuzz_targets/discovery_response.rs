@@ -0,0 +1,8 @@
+#![no_main]
+
+use goodwe::discovery;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = discovery::decode_response(data);
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use goodwe::identify;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = identify::decode_response(data);
+});
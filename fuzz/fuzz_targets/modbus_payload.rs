@@ -0,0 +1,11 @@
+#![no_main]
+
+use goodwe::metrics::modbus;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Use the first byte as the number of requested registers
+    if let Some((count, frame)) = data.split_first() {
+        let _ = modbus::get_payload(frame, modbus::DEFAULT_ADDR, *count as u16);
+    }
+});
@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use crate::{
+    discovery::{self, DiscoveredInverter},
+    identify::{IdResponse, RequestError},
+    metrics::{
+        self,
+        connection::Connection,
+        flow::EnergyFlow,
+        modbus,
+        snapshot::{self, BmsData, MeterData, RunningData},
+        MetricSet, MetricsError,
+    },
+    safety::WriteOptions,
+    settings::{
+        battery::{self, BatterySetting, BatterySettings},
+        eco_schedule::{self, EcoSchedule},
+        export_limit::{self, ExportLimit},
+        work_mode::{self, WorkMode},
+        SettingsError,
+    },
+    transport::TransportOptions,
+};
+
+/// How long to wait for the answer to the identification query
+pub const IDENTIFY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long to keep listening for answers to the discovery trigger
+pub const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// High level access to a single inverter
+///
+/// Every call is a blocking request/response exchange over UDP, so a client
+/// should not be shared between threads without serializing the calls.
+pub struct GoodWeClient {
+    connection: Connection,
+}
+
+impl GoodWeClient {
+    /// Open a connection to the inverter at `target`, which is an IP address
+    /// or host name optionally followed by a port
+    pub fn connect(target: &str) -> Result<Self, MetricsError> {
+        Self::connect_with(target, &TransportOptions::default())
+    }
+
+    /// Like [`GoodWeClient::connect`], but recording or replaying the frames
+    /// as set in `transport`
+    pub fn connect_with(target: &str, transport: &TransportOptions) -> Result<Self, MetricsError> {
+        Ok(Self {
+            connection: Connection::open_with(target, transport)?,
+        })
+    }
+
+    /// Find inverters on the local network by broadcasting the discovery
+    /// trigger to `address`, usually [`discovery::BROADCAST_ADDRESS`]
+    pub fn discover(address: &str) -> std::io::Result<Vec<DiscoveredInverter>> {
+        Self::discover_with(address, &TransportOptions::default())
+    }
+
+    /// Like [`GoodWeClient::discover`], but recording or replaying the
+    /// frames as set in `transport`
+    pub fn discover_with(
+        address: &str,
+        transport: &TransportOptions,
+    ) -> std::io::Result<Vec<DiscoveredInverter>> {
+        discovery::discover_inverters(address, transport, DISCOVERY_TIMEOUT)
+    }
+
+    /// Talk to another Modbus unit than the default one
+    pub fn with_unit(self, addr: u8) -> Self {
+        Self {
+            connection: self.connection.with_unit(addr),
+        }
+    }
+
+    /// Control how settings are written, e.g. dry runs or an audit log
+    pub fn with_write_options(self, write_options: WriteOptions) -> Self {
+        Self {
+            connection: self.connection.with_write_options(write_options),
+        }
+    }
+
+    pub fn target(&self) -> &str {
+        self.connection.target()
+    }
+
+    /// The underlying Modbus connection, for requests not covered here
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Ask the inverter for its serial number and firmware version
+    pub fn identify(&self) -> Result<IdResponse, RequestError> {
+        self.connection.identify(IDENTIFY_TIMEOUT)
+    }
+
+    /// Read `count` consecutive registers starting at `start`
+    pub fn read_registers(&self, start: u16, count: u16) -> Result<Vec<u16>, MetricsError> {
+        let data = self.connection.read_registers(start, count)?;
+        Ok(modbus::to_registers(&data))
+    }
+
+    /// Update all metrics in the set with the current values
+    pub fn read_metrics(&self, ms: &mut MetricSet) -> Result<(), MetricsError> {
+        metrics::read_metrics(&self.connection, ms)
+    }
+
+    /// Read PV, grid, backup, battery and energy values in one request
+    pub fn running_data(&self) -> Result<RunningData, MetricsError> {
+        let data = self
+            .connection
+            .read_registers(snapshot::RUNNING_DATA_START, snapshot::RUNNING_DATA_COUNT)?;
+        RunningData::decode(&data).map_err(MetricsError::MetricReadError)
+    }
+
+    pub fn bms_data(&self) -> Result<BmsData, MetricsError> {
+        let data = self
+            .connection
+            .read_registers(snapshot::BMS_DATA_START, snapshot::BMS_DATA_COUNT)?;
+        BmsData::decode(&data).map_err(MetricsError::MetricReadError)
+    }
+
+    pub fn meter_data(&self) -> Result<MeterData, MetricsError> {
+        let data = self
+            .connection
+            .read_registers(snapshot::METER_DATA_START, snapshot::METER_DATA_COUNT)?;
+        MeterData::decode(&data).map_err(MetricsError::MetricReadError)
+    }
+
+    /// Derive where the energy flows from the running and meter data
+    pub fn energy_flow(&self) -> Result<EnergyFlow, MetricsError> {
+        Ok(EnergyFlow::from_snapshots(
+            &self.running_data()?,
+            &self.meter_data()?,
+        ))
+    }
+
+    pub fn work_mode(&self) -> Result<WorkMode, SettingsError> {
+        work_mode::get_work_mode(&self.connection)
+    }
+
+    pub fn set_work_mode(&self, mode: WorkMode) -> Result<(), SettingsError> {
+        work_mode::set_work_mode(&self.connection, mode)
+    }
+
+    pub fn battery_settings(&self) -> Result<BatterySettings, SettingsError> {
+        battery::get_battery_settings(&self.connection)
+    }
+
+    pub fn set_battery_settings(&self, settings: &[BatterySetting]) -> Result<(), SettingsError> {
+        battery::set_battery_settings(&self.connection, settings)
+    }
+
+    pub fn export_limit(&self) -> Result<ExportLimit, SettingsError> {
+        export_limit::get_export_limit(&self.connection)
+    }
+
+    pub fn set_export_limit(&self, limit: &ExportLimit) -> Result<(), SettingsError> {
+        export_limit::set_export_limit(&self.connection, limit)
+    }
+
+    pub fn eco_schedule(&self) -> Result<EcoSchedule, SettingsError> {
+        eco_schedule::get_eco_schedule(&self.connection)
+    }
+
+    pub fn set_eco_schedule(&self, schedule: &EcoSchedule) -> Result<(), SettingsError> {
+        eco_schedule::set_eco_schedule(&self.connection, schedule)
+    }
+}
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+    use crate::simulator::{Simulator, SimulatorConfig};
+
+    #[test]
+    fn identify_uses_the_connection_transport() {
+        let path = std::env::temp_dir().join(format!(
+            "goodwe-client-recording-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let config = SimulatorConfig::parse("serial_number = \"5020KETU000W0001\"").unwrap();
+        let simulator = Simulator::new(config)
+            .unwrap()
+            .spawn(
+                "127.0.0.1:0".parse().unwrap(),
+                "127.0.0.1:0".parse().unwrap(),
+            )
+            .unwrap();
+        let target = simulator.inverter_addr.to_string();
+        let recording = TransportOptions::record_to(&path).unwrap();
+        let recorded = GoodWeClient::connect_with(&target, &recording)
+            .unwrap()
+            .identify()
+            .unwrap();
+        drop(simulator);
+
+        // Only answered if the query goes through the replaying transport
+        let replay = TransportOptions::replay_from(&path);
+        std::fs::remove_file(&path).unwrap();
+        let replayed = GoodWeClient::connect_with(&target, &replay.unwrap())
+            .unwrap()
+            .identify()
+            .unwrap();
+        assert_eq!(replayed.serial_number, recorded.serial_number);
+    }
+}
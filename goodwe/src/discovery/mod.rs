@@ -0,0 +1,110 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::{net::SocketAddr, str, time::Duration};
+
+use crate::transport::TransportOptions;
+
+#[derive(Debug, Clone)]
+pub struct DiscoveryResponse {
+    pub ip_address: String,
+    pub serial_number: String,
+    pub wifi_name: String,
+}
+
+#[derive(Debug)]
+pub enum DiscoveryError {
+    Encoding,
+    MissingField(&'static str),
+}
+
+impl Display for DiscoveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiscoveryError::Encoding => write!(f, "Response is not valid UTF-8"),
+            DiscoveryError::MissingField(field) => write!(f, "Response lacks the {field}"),
+        }
+    }
+}
+
+impl Error for DiscoveryError {}
+
+/// Decode the answer to the discovery trigger, which is a comma separated
+/// list of IP address, serial number and WiFi name, possibly NUL terminated.
+pub fn decode_response(data: &[u8]) -> Result<DiscoveryResponse, DiscoveryError> {
+    let end = data.iter().position(|x| *x == b'\0').unwrap_or(data.len());
+    let bufstring = str::from_utf8(&data[0..end]).map_err(|_| DiscoveryError::Encoding)?;
+
+    let mut items = bufstring.split_terminator(',');
+    let mut next_item = |field| {
+        items
+            .next()
+            .map(|item| item.to_owned())
+            .ok_or(DiscoveryError::MissingField(field))
+    };
+
+    Ok(DiscoveryResponse {
+        ip_address: next_item("IP address")?,
+        serial_number: next_item("serial number")?,
+        wifi_name: next_item("WiFi name")?,
+    })
+}
+
+/// Where the discovery trigger is sent to by default
+pub const BROADCAST_ADDRESS: &str = "255.255.255.255:48899";
+
+/// A discovery answer along with the address it came from
+pub type DiscoveredInverter = (SocketAddr, Result<DiscoveryResponse, DiscoveryError>);
+
+/// Send the discovery trigger and collect every answer until no more arrive
+/// within `timeout`. Invalid answers are returned too so callers can report them.
+pub fn discover_inverters(
+    address: &str,
+    transport: &TransportOptions,
+    timeout: Duration,
+) -> std::io::Result<Vec<DiscoveredInverter>> {
+    let transport = transport.broadcast(address)?;
+    let request = "WIFIKIT-214028-READ";
+
+    transport.send(request.as_bytes())?;
+
+    let mut inverters = Vec::new();
+    while let Ok((frame, addr)) = transport.receive(timeout) {
+        inverters.push((addr, decode_response(&frame)));
+    }
+
+    Ok(inverters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn decode_response_accepts_any_input(data in proptest::collection::vec(any::<u8>(), 0..1024)) {
+            let _ = decode_response(&data);
+        }
+
+        #[test]
+        fn decode_response_returns_fields(
+            ip_address in "[0-9.]{7,15}",
+            serial_number in "[0-9A-Z]{16}",
+            wifi_name in "[^,\0]+",
+        ) {
+            let data = format!("{ip_address},{serial_number},{wifi_name}\0garbage");
+            let response = decode_response(data.as_bytes()).unwrap();
+            prop_assert_eq!(response.ip_address, ip_address);
+            prop_assert_eq!(response.serial_number, serial_number);
+            prop_assert_eq!(response.wifi_name, wifi_name);
+        }
+    }
+
+    #[test]
+    fn decode_response_rejects_missing_fields() {
+        assert!(matches!(
+            decode_response(b"192.168.1.10,5010KETU000W0000"),
+            Err(DiscoveryError::MissingField(_))
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+use std::{error::Error, fmt::Display, str::from_utf8, time::Duration};
+
+use crate::transport::{self, Transport, TransportOptions};
+
+const ID_QUERY: [u8; 9] = [0xaa, 0x55, 0xc0, 0x7f, 0x01, 0x02, 0x00, 0x02, 0x41];
+
+#[derive(Debug, Clone)]
+pub struct IdResponse {
+    pub serial_number: String,
+    pub firmware: String,
+}
+
+#[derive(Debug)]
+pub enum RequestError {
+    NetworkError(std::io::Error),
+    NoResponse,
+    InvalidResponse(String),
+}
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::NetworkError(ioe) => write!(f, "Network Error: {ioe}"),
+            RequestError::NoResponse => write!(f, "No response received"),
+            RequestError::InvalidResponse(reason) => {
+                write!(f, "Response deemed invalid due to {reason}")
+            }
+        }
+    }
+}
+
+impl Error for RequestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RequestError::NetworkError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+fn map_network_error(e: std::io::Error) -> RequestError {
+    RequestError::NetworkError(e)
+}
+
+pub fn decode_response(data: &[u8]) -> Result<IdResponse, RequestError> {
+    let mut data = data.iter();
+
+    if data.next() != Some(&0xaa) || data.next() != Some(&0x55) {
+        return Err(RequestError::InvalidResponse("Header".to_string()));
+    }
+
+    if data.next() != Some(&0x7f) {
+        return Err(RequestError::InvalidResponse("Source Address".to_string()));
+    }
+
+    if data.next() != Some(&0xc0) {
+        return Err(RequestError::InvalidResponse("Target Address".to_string()));
+    }
+
+    if data.next() != Some(&0x01) {
+        return Err(RequestError::InvalidResponse("Control Code".to_string()));
+    }
+
+    if data.next() != Some(&0x82) {
+        return Err(RequestError::InvalidResponse("Function Code".to_string()));
+    }
+
+    // Contrary to other information, it appears that my GW20K-ET
+    // inverter uses a different protocol/message content than expected.
+    // The only identifiable information is the serial number and the
+    // internal firmware version.
+    // As this is the only inverter I have, I won't implement anything
+    // else for now. If you have data from other models, you're very
+    // welcome to provide it to me, I will extend the code as needed.
+
+    let length = data.next();
+    if length != Some(&76) || data.len() != (76 + 2) {
+        return Err(RequestError::InvalidResponse("Length".to_string()));
+    }
+
+    // The length was checked above, so the fields are always in range
+    let payload = data.as_slice();
+    let serial_number = decode_string(&payload[31..47], "Serial Number")?;
+    let firmware = decode_string(&payload[64..74], "Firmware")?;
+
+    Ok(IdResponse {
+        serial_number,
+        firmware,
+    })
+}
+
+fn decode_string(data: &[u8], field: &str) -> Result<String, RequestError> {
+    match from_utf8(data) {
+        Ok(value) => Ok(value.to_owned()),
+        Err(_) => Err(RequestError::InvalidResponse(format!("{field} encoding"))),
+    }
+}
+
+pub fn query_id(
+    target: &str,
+    transport: &TransportOptions,
+    timeout: Duration,
+) -> Result<IdResponse, RequestError> {
+    let transport = transport
+        .connect(&transport::inverter_address(target))
+        .map_err(map_network_error)?;
+    exchange_id(transport.as_ref(), timeout)
+}
+
+/// Ask for the identification over an already open transport
+pub(crate) fn exchange_id(
+    transport: &dyn Transport,
+    timeout: Duration,
+) -> Result<IdResponse, RequestError> {
+    // Late answers to earlier requests would be taken for the identification
+    transport.discard_pending().map_err(map_network_error)?;
+    transport.send(&ID_QUERY).map_err(map_network_error)?;
+
+    match transport.receive(timeout) {
+        Ok((frame, _)) => decode_response(&frame),
+        Err(_) => Err(RequestError::NoResponse),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn response(serial_number: &[u8; 16], firmware: &[u8; 10]) -> Vec<u8> {
+        let mut data = vec![0xaa, 0x55, 0x7f, 0xc0, 0x01, 0x82, 76];
+        let mut payload = [0_u8; 76 + 2];
+        payload[31..47].copy_from_slice(serial_number);
+        payload[64..74].copy_from_slice(firmware);
+        data.extend_from_slice(&payload);
+        data
+    }
+
+    proptest! {
+        #[test]
+        fn decode_response_accepts_any_input(data in proptest::collection::vec(any::<u8>(), 0..200)) {
+            let _ = decode_response(&data);
+        }
+
+        #[test]
+        fn decode_response_accepts_any_fields(serial_number: [u8; 16], firmware: [u8; 10]) {
+            let _ = decode_response(&response(&serial_number, &firmware));
+        }
+
+        #[test]
+        fn decode_response_returns_fields(
+            serial_number in "[0-9A-Z]{16}",
+            firmware in "[0-9A-Z.]{10}",
+        ) {
+            let data = response(
+                serial_number.as_bytes().try_into().unwrap(),
+                firmware.as_bytes().try_into().unwrap(),
+            );
+            let id = decode_response(&data).ok().unwrap();
+            prop_assert_eq!(id.serial_number, serial_number);
+            prop_assert_eq!(id.firmware, firmware);
+        }
+    }
+}
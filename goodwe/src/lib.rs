@@ -0,0 +1,37 @@
+//! Talk to GoodWe solar inverters over the local network.
+//!
+//! The inverters answer the AA55 protocol and Modbus requests over UDP on
+//! port 8899, and announce themselves when a discovery trigger is broadcast
+//! to port 48899. [`GoodWeClient`] wraps the common tasks:
+//!
+//! ```no_run
+//! use goodwe::{metrics::et, GoodWeClient};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = GoodWeClient::connect("192.168.1.10")?;
+//! println!("Serial number: {}", client.identify()?.serial_number);
+//!
+//! let mut metrics = et::base_metrics();
+//! client.read_metrics(&mut metrics)?;
+//! println!("{metrics}");
+//!
+//! println!("Work mode: {}", client.work_mode()?);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Writes are restricted to the registers in [`safety::WRITABLE_REGISTERS`]
+//! and verified by reading them back.
+
+mod client;
+pub mod discovery;
+pub mod identify;
+pub mod metrics;
+pub mod safety;
+pub mod scan;
+pub mod settings;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+pub mod transport;
+
+pub use client::{GoodWeClient, DISCOVERY_TIMEOUT, IDENTIFY_TIMEOUT};
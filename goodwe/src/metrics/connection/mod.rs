@@ -0,0 +1,322 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    identify::{self, IdResponse, RequestError},
+    safety::{self, PlannedWrite, RegisterChange, WriteOptions, WriteStatus},
+    transport::{self, Transport, TransportOptions},
+};
+
+use super::{
+    map_modbus_error, map_network_error,
+    modbus::{self, Command, ExceptionCode, ModbusError},
+    MetricsError,
+};
+
+const BUSY_RETRIES: usize = 3;
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(500);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Modbus connection to a single inverter
+pub struct Connection {
+    transport: Box<dyn Transport>,
+    addr: u8,
+    target: String,
+    write_options: WriteOptions,
+    planned_writes: Mutex<Vec<PlannedWrite>>,
+}
+
+/// A write that passed the allow-list, with the values it replaces
+struct PreparedWrite {
+    start: u16,
+    values: Vec<u16>,
+    command: Command,
+    param: u16,
+    request: Vec<u8>,
+    changes: Vec<RegisterChange>,
+}
+
+impl Connection {
+    pub fn open(target: &str) -> Result<Self, MetricsError> {
+        Self::open_with(target, &TransportOptions::default())
+    }
+
+    /// Open a connection whose frames are recorded or replayed as set in
+    /// `transport`
+    pub fn open_with(target: &str, transport: &TransportOptions) -> Result<Self, MetricsError> {
+        let transport = transport
+            .connect(&transport::inverter_address(target))
+            .map_err(map_network_error)?;
+
+        Ok(Self {
+            transport,
+            addr: modbus::DEFAULT_ADDR,
+            target: target.to_owned(),
+            write_options: WriteOptions::default(),
+            planned_writes: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Talk to another Modbus unit than the default one
+    pub fn with_unit(mut self, addr: u8) -> Self {
+        self.addr = addr;
+        self
+    }
+
+    pub fn with_write_options(mut self, write_options: WriteOptions) -> Self {
+        self.write_options = write_options;
+        self
+    }
+
+    /// Ask the inverter for its serial number and firmware version, over
+    /// the same transport as the Modbus requests
+    pub fn identify(&self, timeout: Duration) -> Result<IdResponse, RequestError> {
+        identify::exchange_id(self.transport.as_ref(), timeout)
+    }
+
+    /// Read `count` registers starting at `start`, returning the raw bytes
+    pub fn read_registers(&self, start: u16, count: u16) -> Result<Vec<u8>, MetricsError> {
+        let cmd = modbus::create_command(Command::ReadMulti, self.addr, start, count);
+        self.exchange(&cmd, |data| modbus::get_payload(data, self.addr, count))
+    }
+
+    /// Write `values` to the registers starting at `start`. With `verify`,
+    /// the registers are read back afterwards and compared to `values`.
+    ///
+    /// Every register has to be on the allow-list. Depending on the write
+    /// options, the write is only planned, has to be confirmed first and is
+    /// recorded in the audit log.
+    pub fn write_registers(
+        &self,
+        start: u16,
+        values: &[u16],
+        verify: bool,
+    ) -> Result<(), MetricsError> {
+        self.write_batch(&[(start, values)], verify)
+    }
+
+    /// Write several blocks of registers, given by their first register and
+    /// values, one after the other. All of them are checked against the
+    /// allow-list and confirmed together before the first one is sent.
+    pub fn write_batch(&self, writes: &[(u16, &[u16])], verify: bool) -> Result<(), MetricsError> {
+        let writes = writes
+            .iter()
+            .map(|(start, values)| self.prepare_write(*start, values))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if self.write_options.dry_run {
+            let mut planned_writes = self.planned_writes.lock().unwrap();
+            planned_writes.extend(writes.into_iter().map(|write| PlannedWrite {
+                changes: write.changes,
+                frame: write.request,
+            }));
+            return Ok(());
+        }
+
+        if let Some(confirm) = &self.write_options.confirm {
+            let changes: Vec<RegisterChange> = writes
+                .iter()
+                .flat_map(|write| write.changes.iter().copied())
+                .collect();
+            if !confirm(&changes) {
+                return Err(MetricsError::WriteCancelled);
+            }
+        }
+
+        for write in &writes {
+            self.send_write(write, verify)?;
+        }
+
+        Ok(())
+    }
+
+    /// The writes skipped so far because of a dry run, in the order they
+    /// would have been sent
+    pub fn take_planned_writes(&self) -> Vec<PlannedWrite> {
+        std::mem::take(&mut self.planned_writes.lock().unwrap())
+    }
+
+    /// Check a write against the allow-list and read the values it replaces
+    fn prepare_write(&self, start: u16, values: &[u16]) -> Result<PreparedWrite, MetricsError> {
+        let (command, param) = match values.len() {
+            0 => return Err(MetricsError::InvalidWrite("No values to write")),
+            1 => (Command::WriteSingle, values[0]),
+            len if len <= modbus::MAX_WRITE_REGISTERS => (Command::WriteMulti, len as u16),
+            _ => return Err(MetricsError::InvalidWrite("Too many values to write")),
+        };
+        // Checked once here, so the registers of the values can't overflow
+        if start.checked_add(values.len() as u16 - 1).is_none() {
+            return Err(MetricsError::InvalidWrite(
+                "Registers to write exceed the address range",
+            ));
+        }
+
+        for (offset, value) in values.iter().enumerate() {
+            let register = start + offset as u16;
+            if !safety::check_write(register, *value) {
+                return Err(MetricsError::WriteNotAllowed {
+                    register,
+                    value: *value,
+                });
+            }
+        }
+
+        let old = modbus::to_registers(&self.read_registers(start, values.len() as u16)?);
+        let changes: Vec<RegisterChange> = values
+            .iter()
+            .zip(old)
+            .enumerate()
+            .map(|(offset, (new, old))| RegisterChange {
+                register: start + offset as u16,
+                old,
+                new: *new,
+            })
+            .collect();
+
+        let request = match command {
+            Command::WriteMulti => modbus::create_write_multi_command(self.addr, start, values),
+            _ => modbus::create_command(command, self.addr, start, param),
+        };
+
+        Ok(PreparedWrite {
+            start,
+            values: values.to_vec(),
+            command,
+            param,
+            request,
+            changes,
+        })
+    }
+
+    /// Send a prepared write and verify it. The audit log gets an entry
+    /// before the frame is sent and another one with the outcome, so writes
+    /// that time out or fail verification are recorded as well.
+    fn send_write(&self, write: &PreparedWrite, verify: bool) -> Result<(), MetricsError> {
+        self.audit(&write.changes, &WriteStatus::Sending)?;
+
+        let result = self
+            .exchange(&write.request, |data| {
+                modbus::check_write_response(
+                    data,
+                    self.addr,
+                    write.command,
+                    write.start,
+                    write.param,
+                )
+            })
+            .and_then(|_| {
+                if verify {
+                    self.verify_write(write)
+                } else {
+                    Ok(())
+                }
+            });
+
+        let status = match &result {
+            Ok(_) => WriteStatus::Applied,
+            Err(e) => WriteStatus::Failed(e.to_string()),
+        };
+        let audited = self.audit(&write.changes, &status);
+        // The error of the write itself is more important than the audit one
+        result?;
+        audited
+    }
+
+    fn verify_write(&self, write: &PreparedWrite) -> Result<(), MetricsError> {
+        let actual =
+            modbus::to_registers(&self.read_registers(write.start, write.values.len() as u16)?);
+        for (offset, (expected, actual)) in write.values.iter().zip(actual).enumerate() {
+            if *expected != actual {
+                return Err(MetricsError::VerificationFailed {
+                    register: write.start + offset as u16,
+                    expected: *expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn audit(&self, changes: &[RegisterChange], status: &WriteStatus) -> Result<(), MetricsError> {
+        match &self.write_options.audit_log {
+            Some(audit_log) => audit_log
+                .record(&self.target, changes, status)
+                .map_err(MetricsError::AuditLogError),
+            None => Ok(()),
+        }
+    }
+
+    /// Send a request and wait for its answer, retrying while the inverter
+    /// reports to be busy.
+    fn exchange<T>(
+        &self,
+        request: &[u8],
+        decode: impl Fn(&[u8]) -> Result<T, ModbusError>,
+    ) -> Result<T, MetricsError> {
+        let mut attempt = 0;
+        loop {
+            // Answers to earlier requests may still arrive after they timed
+            // out, drop everything that is already waiting
+            self.transport
+                .discard_pending()
+                .map_err(map_network_error)?;
+            self.transport.send(request).map_err(map_network_error)?;
+
+            match self.receive(&decode) {
+                Err(MetricsError::ModbusError(ModbusError::Exception(
+                    ExceptionCode::SlaveDeviceBusy,
+                ))) if attempt < BUSY_RETRIES => {
+                    attempt += 1;
+                    thread::sleep(BUSY_RETRY_DELAY);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Wait for the answer to the last request, skipping datagrams that
+    /// answer a different request until the timeout expires.
+    fn receive<T>(
+        &self,
+        decode: impl Fn(&[u8]) -> Result<T, ModbusError>,
+    ) -> Result<T, MetricsError> {
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        let mut mismatch = None;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let frame = match self.transport.receive(remaining) {
+                Ok((frame, _)) => frame,
+                // Report the mismatch, it is more helpful than the timeout
+                Err(e) => return Err(mismatch.map_or(map_network_error(e), map_modbus_error)),
+            };
+
+            match decode(&frame) {
+                Err(e @ ModbusError::ResponseMismatch { .. }) => mismatch = Some(e),
+                result => return result.map_err(map_modbus_error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_writes_beyond_the_last_register() {
+        // Rejected before anything is sent, nobody has to listen
+        let conn = Connection::open("127.0.0.1:1").unwrap();
+        assert!(matches!(
+            conn.write_registers(65535, &[1, 2], false),
+            Err(MetricsError::InvalidWrite(_))
+        ));
+    }
+}
@@ -0,0 +1,1736 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    fmt::Display,
+};
+
+use super::{
+    expression::Reference,
+    planner::{self, PlanOptions, ReadBlock},
+};
+
+const METRIC_NAME_PREFIX: &str = "goodwe_";
+
+pub struct MetricSet {
+    pub(crate) metrics: Vec<Box<dyn Metric>>,
+    /// Registers the inverter refused to read, they are skipped from then on
+    unsupported: HashSet<u16>,
+}
+
+impl MetricSet {
+    pub fn new(metrics: Vec<Box<dyn Metric>>) -> Self {
+        Self {
+            metrics,
+            unsupported: HashSet::new(),
+        }
+    }
+
+    pub fn mark_unsupported(&mut self, register: u16) {
+        self.unsupported.insert(register);
+    }
+
+    fn supported_metrics(&self) -> impl Iterator<Item = &Box<dyn Metric>> {
+        self.metrics
+            .iter()
+            .filter(|m| !self.unsupported.contains(&m.get_register()))
+    }
+
+    /// Register ranges, as start register and width, of all supported
+    /// metrics within the given block
+    pub fn ranges_in(&self, block: &ReadBlock) -> Vec<(u16, u16)> {
+        self.supported_metrics()
+            .map(|m| (m.get_register(), m.get_width()))
+            .filter(|(register, width)| block.contains(*register, *width))
+            .collect()
+    }
+
+    /// Read the values of all metrics that are completely contained in the
+    /// given block. `data` holds the registers of the block.
+    pub fn read_block(&mut self, block: &ReadBlock, data: &[u8]) -> Result<(), MetricReadError> {
+        for metric in &mut self.metrics {
+            let metric: &mut dyn Metric = metric.as_mut();
+            if block.contains(metric.get_register(), metric.get_width()) {
+                metric.read_data(block.start, data)?
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Determine the read requests needed to fetch all metrics of the set.
+    /// Requests never include the registers of unsupported metrics, the
+    /// inverter would refuse the whole block otherwise.
+    pub fn plan_reads(&self, options: &PlanOptions) -> Vec<ReadBlock> {
+        let excluded: HashSet<u16> = self
+            .metrics
+            .iter()
+            .filter(|m| self.unsupported.contains(&m.get_register()))
+            .flat_map(|m| m.get_register()..m.get_register() + m.get_width())
+            .collect();
+
+        planner::plan_reads(
+            self.supported_metrics()
+                .map(|m| (m.get_register(), m.get_width())),
+            &excluded,
+            options,
+        )
+    }
+
+    /// Value of the supported metric with the given name, including the
+    /// prefix, and label
+    pub fn value(&self, name: &str, key: &str, label: &str) -> Option<f64> {
+        self.supported_metrics()
+            .find(|m| m.get_name() == name && m.get_label(key) == Some(label))
+            .and_then(|m| m.get_value())
+    }
+
+    /// All metrics with the name of the reference and carrying all of its labels
+    pub(crate) fn matching<'a>(
+        &'a self,
+        reference: &'a Reference,
+    ) -> impl Iterator<Item = &'a Box<dyn Metric>> {
+        self.metrics.iter().filter(|m| {
+            m.get_name() == reference.name
+                && reference
+                    .labels
+                    .iter()
+                    .all(|(key, value)| m.get_label(key) == Some(value.as_str()))
+        })
+    }
+}
+
+impl Display for MetricSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Exposition(vec![self]).fmt(f)
+    }
+}
+
+/// Prometheus text format of several metric sets. A metric family may be
+/// spread over several sets, e.g. the temperatures of the inverter and the
+/// battery, so every family is rendered once: its TYPE line followed by the
+/// samples of all sets.
+pub struct Exposition<'a>(pub Vec<&'a MetricSet>);
+
+impl Display for Exposition<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Sorted by name, so the output does not change between scrapes
+        let mut families: BTreeMap<String, (MetricType, Vec<&dyn Metric>)> = BTreeMap::new();
+        for metric_set in &self.0 {
+            for metric in metric_set.supported_metrics() {
+                families
+                    .entry(metric.get_name())
+                    .or_insert_with(|| (metric.get_type(), Vec::new()))
+                    .1
+                    .push(metric.as_ref());
+            }
+        }
+
+        for (name, (metric_type, metrics)) in families {
+            writeln!(f, "# TYPE {} {}", name, metric_type)?;
+            for metric in metrics {
+                // Bitfields without active flags have no samples at all
+                let samples = metric.to_string();
+                if !samples.is_empty() {
+                    writeln!(f, "{}", samples)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct BaseMetric {
+    #[allow(dead_code)]
+    metric_type: MetricType,
+    metric_name: String,
+    labels: Vec<KV<String, String>>,
+    register: u16,
+}
+
+impl BaseMetric {
+    pub fn new(
+        metric_type: MetricType,
+        metric_name: String,
+        labels: Vec<KV<String, String>>,
+        register: u16,
+    ) -> Self {
+        Self {
+            metric_type,
+            metric_name,
+            labels,
+            register,
+        }
+    }
+
+    pub fn get_register(&self) -> u16 {
+        self.register
+    }
+
+    pub fn label(&self, key: &str) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|kv| kv.key == key)
+            .map(|kv| kv.value.as_str())
+    }
+}
+
+impl BaseMetric {
+    /// Like `Display`, with more labels added to the metric's own
+    fn fmt_with_labels(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        extra: &[(&str, &str)],
+    ) -> std::fmt::Result {
+        write!(f, "{} {{", self.metric_name)?;
+        for kv in &self.labels {
+            write!(f, "{}, ", kv)?;
+        }
+        for (key, value) in extra {
+            write!(f, "{}, ", KV::new(key, value))?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl Display for BaseMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {{", self.metric_name)?;
+        for kv in &self.labels {
+            write!(f, "{}, ", kv)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+pub trait Metric: Display + Send {
+    #[allow(dead_code)]
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError>;
+    fn get_register(&self) -> u16;
+    /// Number of 16 bit registers the metric spans
+    fn get_width(&self) -> u16;
+    fn get_name(&self) -> String;
+    fn get_type(&self) -> MetricType;
+    /// The value read last, if any
+    fn get_value(&self) -> Option<f64>;
+    fn get_label(&self, key: &str) -> Option<&str>;
+}
+
+#[derive(Clone, Copy)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+}
+
+impl Display for MetricType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetricType::Counter => write!(f, "counter"),
+            MetricType::Gauge => write!(f, "gauge"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MetricReadError {
+    OutOfBounds,
+    #[allow(dead_code)]
+    GeneralError,
+}
+
+impl Display for MetricReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetricReadError::OutOfBounds => write!(f, "MetricReadError::OutOfBounds"),
+            MetricReadError::GeneralError => write!(f, "MetricReadError::GeneralError"),
+        }
+    }
+}
+
+impl Error for MetricReadError {}
+
+pub struct KV<K, V>
+where
+    K: Display,
+    V: Display,
+{
+    key: K,
+    value: V,
+}
+
+impl<K, V> Display for KV<K, V>
+where
+    K: Display,
+    V: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}=\"{}\"", self.key, self.value)
+    }
+}
+
+impl<K, V> KV<K, V>
+where
+    K: Display,
+    V: Display,
+{
+    #[allow(dead_code)]
+    pub fn new(key: K, value: V) -> Self {
+        Self { key, value }
+    }
+}
+
+fn get_register_bytes<const WIDTH: usize>(
+    data: &[u8],
+    register: usize,
+    base_register: usize,
+) -> Result<[u8; WIDTH], MetricReadError> {
+    if register < base_register {
+        return Err(MetricReadError::OutOfBounds);
+    }
+    let offset = (register - base_register) * 2;
+    if offset + WIDTH > data.len() {
+        return Err(MetricReadError::OutOfBounds);
+    }
+
+    let mut retval: [u8; WIDTH] = [0_u8; WIDTH];
+    retval.copy_from_slice(&data[offset..(offset + WIDTH)]);
+    Ok(retval)
+}
+
+pub struct Voltage {
+    base: BaseMetric,
+    value: Option<f32>,
+}
+
+impl Voltage {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Gauge, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for Voltage {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<2>(data, self.base.register as usize, base_register as usize)?;
+        let value = i16::from_be_bytes(value);
+        self.value = Some(value as f32 / 10.0);
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for Voltage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(f32::NAN))
+    }
+}
+
+pub struct Current {
+    base: BaseMetric,
+    value: Option<f32>,
+}
+
+impl Current {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Gauge, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for Current {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<2>(data, self.base.register as usize, base_register as usize)?;
+        let value = i16::from_be_bytes(value);
+        self.value = Some(value as f32 / 10.0);
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for Current {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(f32::NAN))
+    }
+}
+
+pub struct Power {
+    base: BaseMetric,
+    value: Option<i16>,
+}
+
+impl Power {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Gauge, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for Power {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<2>(data, self.base.register as usize, base_register as usize)?;
+        self.value = Some(i16::from_be_bytes(value));
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for Power {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(0))
+    }
+}
+
+/// Power that can't be negative, e.g. a limit, using the full range of the
+/// register
+pub struct UnsignedPower {
+    base: BaseMetric,
+    value: Option<u16>,
+}
+
+impl UnsignedPower {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Gauge, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for UnsignedPower {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<2>(data, self.base.register as usize, base_register as usize)?;
+        self.value = Some(u16::from_be_bytes(value));
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for UnsignedPower {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(0))
+    }
+}
+
+pub struct LargePower {
+    base: BaseMetric,
+    value: Option<i32>,
+}
+
+impl LargePower {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Gauge, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for LargePower {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<4>(data, self.base.register as usize, base_register as usize)?;
+        self.value = Some(i32::from_be_bytes(value));
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        2
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for LargePower {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(0))
+    }
+}
+
+pub struct Frequency {
+    base: BaseMetric,
+    value: Option<f32>,
+}
+
+impl Frequency {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Gauge, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for Frequency {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<2>(data, self.base.register as usize, base_register as usize)?;
+        let value = i16::from_be_bytes(value);
+        self.value = Some(value as f32 / 100.0);
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for Frequency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(f32::NAN))
+    }
+}
+
+pub struct Percentage {
+    base: BaseMetric,
+    value: Option<u16>,
+}
+
+impl Percentage {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Gauge, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for Percentage {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<2>(data, self.base.register as usize, base_register as usize)?;
+        self.value = Some(u16::from_be_bytes(value));
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for Percentage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(u16::MIN))
+    }
+}
+
+pub struct Temperature {
+    base: BaseMetric,
+    value: Option<f32>,
+}
+
+impl Temperature {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Gauge, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for Temperature {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<2>(data, self.base.register as usize, base_register as usize)?;
+        let value = i16::from_be_bytes(value);
+        self.value = Some(value as f32 / 10.0);
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for Temperature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(f32::NAN))
+    }
+}
+
+pub struct Energy {
+    base: BaseMetric,
+    value: Option<f32>,
+}
+
+impl Energy {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Counter, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for Energy {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<2>(data, self.base.register as usize, base_register as usize)?;
+        let value = i16::from_be_bytes(value);
+        self.value = Some(value as f32 / 10.0);
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for Energy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(f32::NAN))
+    }
+}
+
+pub struct LargeEnergy {
+    base: BaseMetric,
+    value: Option<f32>,
+}
+
+impl LargeEnergy {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Counter, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for LargeEnergy {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<4>(data, self.base.register as usize, base_register as usize)?;
+        let value = i32::from_be_bytes(value);
+        self.value = Some(value as f32 / 10.0);
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        2
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for LargeEnergy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(f32::NAN))
+    }
+}
+
+pub struct FloatEnergy {
+    base: BaseMetric,
+    value: Option<f32>,
+}
+
+impl FloatEnergy {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Counter, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for FloatEnergy {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<4>(data, self.base.register as usize, base_register as usize)?;
+        self.value = Some(f32::from_be_bytes(value));
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        2
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for FloatEnergy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(f32::NAN))
+    }
+}
+
+pub struct Integer {
+    base: BaseMetric,
+    value: Option<i16>,
+}
+
+impl Integer {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Counter, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+
+    /// Like `easy`, for codes and settings that can go up and down rather
+    /// than counts
+    pub fn gauge(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        let mut metric = Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        );
+        metric.base.metric_type = MetricType::Gauge;
+        Box::new(metric)
+    }
+}
+
+impl Metric for Integer {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<2>(data, self.base.register as usize, base_register as usize)?;
+        self.value = Some(i16::from_be_bytes(value));
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for Integer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(i16::MIN))
+    }
+}
+
+pub struct Decimal {
+    base: BaseMetric,
+    value: Option<f32>,
+}
+
+impl Decimal {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Counter, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for Decimal {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<2>(data, self.base.register as usize, base_register as usize)?;
+        let value = i16::from_be_bytes(value);
+        self.value = Some(value as f32 / 1000.0);
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.base, self.value.unwrap_or(f32::NAN))
+    }
+}
+
+/// Unsigned two-word counter without scaling, e.g. operating hours
+pub struct LargeInteger {
+    base: BaseMetric,
+    value: Option<u32>,
+}
+
+impl LargeInteger {
+    pub fn new(register: u16, metric_name: &str, labels: Vec<KV<String, String>>) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Counter, metric_name, labels, register);
+        Self { base, value: None }
+    }
+
+    pub fn easy(register: u16, metric_name: &str, key: &str, value: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(
+            register,
+            metric_name,
+            vec![KV {
+                key: key.to_string(),
+                value: value.to_string(),
+            }],
+        ))
+    }
+}
+
+impl Metric for LargeInteger {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let value =
+            get_register_bytes::<4>(data, self.base.register as usize, base_register as usize)?;
+        self.value = Some(u32::from_be_bytes(value));
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        2
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for LargeInteger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value {
+            Some(value) => write!(f, "{} {}", self.base, value),
+            None => write!(f, "{} NaN", self.base),
+        }
+    }
+}
+
+/// Field of the inverter clock, which spans three registers with one byte
+/// each for year (since 2000), month, day, hour, minute and second
+#[derive(Clone, Copy)]
+pub enum ClockField {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+impl ClockField {
+    fn name(self) -> &'static str {
+        match self {
+            ClockField::Year => "year",
+            ClockField::Month => "month",
+            ClockField::Day => "day",
+            ClockField::Hour => "hour",
+            ClockField::Minute => "minute",
+            ClockField::Second => "second",
+        }
+    }
+}
+
+/// One field of the inverter clock, labelled with `part`. The clock is kept
+/// in local time without a time zone, so it is exported as it is instead of
+/// being turned into a timestamp. An unset clock reads as month and day 0.
+pub struct Clock {
+    base: BaseMetric,
+    field: ClockField,
+    value: Option<u16>,
+}
+
+impl Clock {
+    /// `register` is the first register of the clock
+    pub fn new(register: u16, field: ClockField, metric_name: &str) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let labels = vec![KV::new("part".to_owned(), field.name().to_owned())];
+        let register = register + field as u16 / 2;
+        let base = BaseMetric::new(MetricType::Gauge, metric_name, labels, register);
+        Self {
+            base,
+            field,
+            value: None,
+        }
+    }
+
+    pub fn easy(register: u16, field: ClockField, metric_name: &str) -> Box<dyn Metric> {
+        Box::new(Self::new(register, field, metric_name))
+    }
+}
+
+impl Metric for Clock {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let bytes =
+            get_register_bytes::<2>(data, self.base.register as usize, base_register as usize)?;
+        let byte = bytes[self.field as usize % 2] as u16;
+        self.value = Some(match self.field {
+            ClockField::Year => 2000 + byte,
+            _ => byte,
+        });
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value {
+            Some(value) => write!(f, "{} {}", self.base, value),
+            None => write!(f, "{} NaN", self.base),
+        }
+    }
+}
+
+/// Name of the state reported when the register holds a value missing from
+/// the table
+const UNKNOWN_STATE: &str = "Unknown";
+
+/// Register holding one of several modes or states. It is rendered as a
+/// state set, one sample per state with the value 1 for the current state
+/// and 0 for all others.
+pub struct State {
+    base: BaseMetric,
+    source: StateSource,
+    states: Vec<(u16, &'static str)>,
+    value: Option<u16>,
+}
+
+/// Where the state code comes from
+enum StateSource {
+    /// The register holds the code
+    Register,
+    /// The code is derived from the sign of a power value, see
+    /// `State::power_direction`
+    PowerDirection,
+}
+
+/// Powers closer to zero than this, in W, count as no power flowing
+const POWER_DIRECTION_DEADBAND: i16 = 90;
+
+impl State {
+    pub fn new(register: u16, metric_name: &str, states: &[(u16, &'static str)]) -> Self {
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let base = BaseMetric::new(MetricType::Gauge, metric_name, vec![], register);
+        Self {
+            base,
+            source: StateSource::Register,
+            states: states.to_vec(),
+            value: None,
+        }
+    }
+
+    pub fn easy(
+        register: u16,
+        metric_name: &str,
+        states: &[(u16, &'static str)],
+    ) -> Box<dyn Metric> {
+        Box::new(Self::new(register, metric_name, states))
+    }
+
+    /// State of a signed power: code 0 while the power is close to
+    /// zero, 1 while it is positive and 2 while it is negative
+    pub fn power_direction(
+        register: u16,
+        metric_name: &str,
+        states: &[(u16, &'static str)],
+    ) -> Box<dyn Metric> {
+        Box::new(Self {
+            source: StateSource::PowerDirection,
+            ..Self::new(register, metric_name, states)
+        })
+    }
+
+    /// Name of the current state, if the register was read
+    pub fn state(&self) -> Option<&'static str> {
+        let value = self.value?;
+        Some(
+            self.states
+                .iter()
+                .find(|(code, _)| *code == value)
+                .map_or(UNKNOWN_STATE, |(_, name)| name),
+        )
+    }
+}
+
+impl Metric for State {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let (register, base_register) = (self.base.register as usize, base_register as usize);
+        self.value = Some(match self.source {
+            StateSource::Register => {
+                u16::from_be_bytes(get_register_bytes::<2>(data, register, base_register)?)
+            }
+            StateSource::PowerDirection => {
+                match i16::from_be_bytes(get_register_bytes::<2>(data, register, base_register)?) {
+                    power if power >= POWER_DIRECTION_DEADBAND => 1,
+                    power if power <= -POWER_DIRECTION_DEADBAND => 2,
+                    _ => 0,
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    fn get_width(&self) -> u16 {
+        1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    /// The raw value, so derived metrics can compare it
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let current = self.state();
+        let mut names: Vec<&str> = self.states.iter().map(|(_, name)| *name).collect();
+        if current == Some(UNKNOWN_STATE) {
+            names.push(UNKNOWN_STATE);
+        }
+
+        for (index, name) in names.into_iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            self.base.fmt_with_labels(f, &[("state", name)])?;
+            write!(f, " {}", u8::from(current == Some(name)))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Flag of a bitfield as bit number, name and description
+pub type Flag = (u8, &'static str, &'static str);
+
+/// Error or warning flags spread over one or more registers, which don't
+/// have to be adjacent. One sample is rendered for every active flag.
+pub struct Bitfield {
+    base: BaseMetric,
+    /// Registers from the least to the most significant word
+    words: Vec<u16>,
+    flags: &'static [Flag],
+    value: Option<u64>,
+}
+
+impl Bitfield {
+    /// `words` lists the registers from the lowest to the highest word, at
+    /// most four of them fit the 64 bit value
+    pub fn new(words: &[u16], metric_name: &str, flags: &'static [Flag]) -> Self {
+        assert!(
+            !words.is_empty() && words.len() <= 4,
+            "bitfield {metric_name} needs one to four words"
+        );
+        let mut metric_name: String = metric_name.to_owned();
+        metric_name.insert_str(0, METRIC_NAME_PREFIX);
+        let register = words.iter().copied().min().unwrap_or_default();
+        let base = BaseMetric::new(MetricType::Gauge, metric_name, vec![], register);
+        Self {
+            base,
+            words: words.to_vec(),
+            flags,
+            value: None,
+        }
+    }
+
+    pub fn easy(words: &[u16], metric_name: &str, flags: &'static [Flag]) -> Box<dyn Metric> {
+        Box::new(Self::new(words, metric_name, flags))
+    }
+
+    /// Bit numbers, names and descriptions of all active flags. Bits missing
+    /// from the table are named after their number.
+    pub fn active_flags(&self) -> Vec<(u8, String, &'static str)> {
+        let value = self.value.unwrap_or_default();
+        (0..self.words.len() as u8 * 16)
+            .filter(|bit| value & (1 << bit) != 0)
+            .map(|bit| match self.flags.iter().find(|flag| flag.0 == bit) {
+                Some((_, name, description)) => (bit, name.to_string(), *description),
+                None => (bit, format!("bit_{bit}"), "Unknown"),
+            })
+            .collect()
+    }
+}
+
+impl Metric for Bitfield {
+    fn read_data(&mut self, base_register: u16, data: &[u8]) -> Result<(), MetricReadError> {
+        let mut value = 0;
+        for (index, register) in self.words.iter().enumerate() {
+            let word = get_register_bytes::<2>(data, *register as usize, base_register as usize)?;
+            value |= (u16::from_be_bytes(word) as u64) << (16 * index);
+        }
+        self.value = Some(value);
+
+        Ok(())
+    }
+
+    fn get_register(&self) -> u16 {
+        self.base.get_register()
+    }
+
+    /// Spans all registers from the first to the last word
+    fn get_width(&self) -> u16 {
+        let last = self.words.iter().copied().max().unwrap_or_default();
+        last - self.base.get_register() + 1
+    }
+
+    fn get_name(&self) -> String {
+        self.base.metric_name.clone()
+    }
+
+    fn get_type(&self) -> MetricType {
+        self.base.metric_type
+    }
+
+    /// All words combined, so derived metrics can test single bits
+    fn get_value(&self) -> Option<f64> {
+        self.value.map(|value| value as f64)
+    }
+
+    fn get_label(&self, key: &str) -> Option<&str> {
+        self.base.label(key)
+    }
+}
+
+impl Display for Bitfield {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, (_, name, description)) in self.active_flags().into_iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            self.base
+                .fmt_with_labels(f, &[("flag", &name), ("description", description)])?;
+            write!(f, " 1")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: &[(u16, &str)] = &[(0, "Idle"), (1, "Exporting"), (2, "Importing")];
+
+    fn read_state(value: u16) -> State {
+        let mut state = State::new(35139, "grid_direction", MODES);
+        state.read_data(35138, &[0, 0, 0, value as u8]).unwrap();
+        state
+    }
+
+    #[test]
+    fn state_renders_state_set() {
+        assert_eq!(
+            read_state(2).to_string(),
+            "goodwe_grid_direction {state=\"Idle\", } 0\n\
+             goodwe_grid_direction {state=\"Exporting\", } 0\n\
+             goodwe_grid_direction {state=\"Importing\", } 1"
+        );
+        assert_eq!(read_state(2).get_value(), Some(2.0));
+    }
+
+    #[test]
+    fn state_reports_unknown_values() {
+        let state = read_state(9);
+        assert_eq!(state.state(), Some("Unknown"));
+        assert!(state
+            .to_string()
+            .ends_with("goodwe_grid_direction {state=\"Unknown\", } 1"));
+    }
+
+    const FLAGS: &[Flag] = &[
+        (0, "charge_over_voltage", "Charging over-voltage"),
+        (2, "cell_temperature_high", "Cell temperature high"),
+    ];
+
+    #[test]
+    fn bitfield_combines_words() {
+        // Low word at 37006, high word at 37011
+        let mut bitfield = Bitfield::new(&[37006, 37011], "battery_error", FLAGS);
+        assert_eq!(bitfield.get_register(), 37006);
+        assert_eq!(bitfield.get_width(), 6);
+
+        let mut data = [0_u8; 12];
+        data[1] = 0b101;
+        data[10] = 0x80;
+        bitfield.read_data(37006, &data).unwrap();
+
+        assert_eq!(
+            bitfield.get_value(),
+            Some(((0x8000_u64 << 16) | 0b101) as f64)
+        );
+        assert_eq!(
+            bitfield.to_string(),
+            "goodwe_battery_error {flag=\"charge_over_voltage\", description=\"Charging over-voltage\", } 1\n\
+             goodwe_battery_error {flag=\"cell_temperature_high\", description=\"Cell temperature high\", } 1\n\
+             goodwe_battery_error {flag=\"bit_31\", description=\"Unknown\", } 1"
+        );
+    }
+
+    #[test]
+    fn bitfield_without_flags_has_no_samples() {
+        let mut bitfield = Bitfield::new(&[37006, 37011], "battery_error", FLAGS);
+        assert_eq!(bitfield.to_string(), "");
+
+        bitfield.read_data(37006, &[0; 12]).unwrap();
+        assert_eq!(bitfield.to_string(), "");
+        assert_eq!(bitfield.get_value(), Some(0.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "needs one to four words")]
+    fn bitfield_rejects_more_than_64_bits() {
+        Bitfield::new(&[1, 2, 3, 4, 5], "too_wide", FLAGS);
+    }
+
+    #[test]
+    fn plans_skip_unsupported_registers() {
+        let mut ms = MetricSet::new(vec![
+            Integer::easy(100, "a", "none", "none"),
+            LargeEnergy::easy(102, "b", "none", "none"),
+            Integer::easy(106, "c", "none", "none"),
+        ]);
+        let options = PlanOptions::default();
+        assert_eq!(
+            ms.plan_reads(&options),
+            vec![ReadBlock {
+                start: 100,
+                count: 7
+            }]
+        );
+
+        ms.mark_unsupported(102);
+        let blocks = ms.plan_reads(&options);
+        assert_eq!(
+            blocks,
+            vec![
+                ReadBlock {
+                    start: 100,
+                    count: 1
+                },
+                ReadBlock {
+                    start: 106,
+                    count: 1
+                }
+            ]
+        );
+        assert!(!blocks
+            .iter()
+            .any(|block| block.contains(102, 1) || block.contains(103, 1)));
+    }
+
+    #[test]
+    fn unsigned_power_uses_full_range() {
+        let mut power = UnsignedPower::new(47510, "export_limit_watts", vec![]);
+        power.read_data(47510, &40000_u16.to_be_bytes()).unwrap();
+        assert_eq!(power.get_value(), Some(40000.0));
+    }
+
+    #[test]
+    fn state_without_value_has_no_current_state() {
+        let state = State::new(35139, "grid_direction", MODES);
+        assert_eq!(state.state(), None);
+        assert!(!state.to_string().contains("} 1"));
+    }
+
+    #[test]
+    fn exposition_renders_families_once() {
+        let read = |register: u16, sensor: &str, value: i16| {
+            let mut ms = MetricSet::new(vec![Temperature::easy(
+                register,
+                "temperature_celsius",
+                "sensor",
+                sensor,
+            )]);
+            ms.read_block(
+                &ReadBlock {
+                    start: register,
+                    count: 1,
+                },
+                &value.to_be_bytes(),
+            )
+            .unwrap();
+            ms
+        };
+        let (inverter, battery) = (read(35174, "Air", 352), read(37003, "Battery", 254));
+
+        assert_eq!(
+            Exposition(vec![&inverter, &battery]).to_string(),
+            "# TYPE goodwe_temperature_celsius gauge\n\
+             goodwe_temperature_celsius {sensor=\"Air\", } 35.2\n\
+             goodwe_temperature_celsius {sensor=\"Battery\", } 25.4\n"
+        );
+    }
+}
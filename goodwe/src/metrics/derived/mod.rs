@@ -0,0 +1,475 @@
+use std::{collections::BTreeMap, error::Error, fmt::Display};
+
+use serde::Deserialize;
+
+use super::{
+    definitions::{BaseMetric, MetricSet, MetricType, KV},
+    expression::{Expression, Reference},
+    flow, METRIC_UP,
+};
+
+/// Metrics computed from other metrics, as defined in the configuration:
+///
+/// ```toml
+/// [[metric]]
+/// name = "goodwe_east_roof_pv_watts"
+/// labels = { roof = "east" }
+/// expression = 'goodwe_power_pv_watts{mppt="pv1"} + goodwe_power_pv_watts{mppt="pv3"}'
+/// ```
+///
+/// Expressions may refer to the metrics read from the inverter and to the
+/// derived metrics defined before them.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DerivedMetrics {
+    #[serde(rename = "metric", default)]
+    pub metrics: Vec<DerivedMetric>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DerivedMetric {
+    pub name: String,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    pub expression: Expression,
+}
+
+#[derive(Debug)]
+pub enum DerivedMetricError {
+    InvalidName(String),
+    /// Quotes, backslashes and line breaks would break the exposition format
+    InvalidLabelValue(String),
+    /// A metric of that name is already exported
+    NameInUse(String),
+    /// Another derived metric has the same name and labels
+    Duplicate(String),
+    /// No metric matches the reference of the derived metric
+    UnknownReference {
+        metric: String,
+        reference: String,
+    },
+    /// Several metrics match the reference, more labels are needed
+    AmbiguousReference {
+        metric: String,
+        reference: String,
+    },
+}
+
+impl Display for DerivedMetricError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DerivedMetricError::InvalidName(name) => {
+                write!(f, "'{name}' is not a valid metric or label name")
+            }
+            DerivedMetricError::InvalidLabelValue(value) => write!(
+                f,
+                "Label value '{value}' must not contain quotes, backslashes or line breaks"
+            ),
+            DerivedMetricError::NameInUse(name) => {
+                write!(f, "Derived metric {name} is already exported")
+            }
+            DerivedMetricError::Duplicate(name) => {
+                write!(
+                    f,
+                    "Derived metric {name} is defined twice with the same labels"
+                )
+            }
+            DerivedMetricError::UnknownReference { metric, reference } => {
+                write!(
+                    f,
+                    "Derived metric {metric} refers to unknown metric {reference}"
+                )
+            }
+            DerivedMetricError::AmbiguousReference { metric, reference } => write!(
+                f,
+                "Derived metric {metric} refers to {reference}, which matches several metrics"
+            ),
+        }
+    }
+}
+
+impl Error for DerivedMetricError {}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+fn is_valid_label_value(value: &str) -> bool {
+    !value.contains(['"', '\\', '\n'])
+}
+
+impl DerivedMetric {
+    fn matches(&self, reference: &Reference) -> bool {
+        self.name == reference.name
+            && reference
+                .labels
+                .iter()
+                .all(|(key, value)| self.labels.get(key) == Some(value))
+    }
+}
+
+impl DerivedMetrics {
+    /// Make sure every derived metric has a new name and labels, and every
+    /// reference matches exactly one metric of the given sets or one of the
+    /// derived metrics defined before
+    pub fn validate(&self, metric_sets: &[MetricSet]) -> Result<(), DerivedMetricError> {
+        let exported: Vec<String> = metric_sets
+            .iter()
+            .flat_map(|ms| ms.metrics.iter().map(|m| m.get_name()))
+            .chain(flow::METRIC_NAMES.map(str::to_owned))
+            .chain([METRIC_UP.to_owned()])
+            .collect();
+
+        for (index, metric) in self.metrics.iter().enumerate() {
+            for name in std::iter::once(&metric.name).chain(metric.labels.keys()) {
+                if !is_valid_name(name) {
+                    return Err(DerivedMetricError::InvalidName(name.clone()));
+                }
+            }
+            if let Some(value) = metric.labels.values().find(|v| !is_valid_label_value(v)) {
+                return Err(DerivedMetricError::InvalidLabelValue(value.clone()));
+            }
+            if exported.contains(&metric.name) {
+                return Err(DerivedMetricError::NameInUse(metric.name.clone()));
+            }
+            if self.metrics[..index]
+                .iter()
+                .any(|m| m.name == metric.name && m.labels == metric.labels)
+            {
+                return Err(DerivedMetricError::Duplicate(metric.name.clone()));
+            }
+
+            for reference in metric.expression.references() {
+                let matching = metric_sets
+                    .iter()
+                    .map(|ms| ms.matching(reference).count())
+                    .sum::<usize>()
+                    + self.metrics[..index]
+                        .iter()
+                        .filter(|m| m.matches(reference))
+                        .count();
+                let (metric, reference) = (metric.name.clone(), reference.to_string());
+                match matching {
+                    0 => return Err(DerivedMetricError::UnknownReference { metric, reference }),
+                    1 => (),
+                    _ => return Err(DerivedMetricError::AmbiguousReference { metric, reference }),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compute all derived metrics from the values just read into the sets.
+    /// Values that are missing, e.g. because the inverter doesn't support a
+    /// register, turn into NaN.
+    pub fn evaluate(&self, metric_sets: &[MetricSet]) -> DerivedValues<'_> {
+        let mut values: Vec<f64> = Vec::with_capacity(self.metrics.len());
+        for metric in &self.metrics {
+            let lookup = |reference: &Reference| {
+                let derived = self
+                    .metrics
+                    .iter()
+                    .zip(&values)
+                    .find(|(m, _)| m.matches(reference))
+                    .map(|(_, value)| *value);
+                derived
+                    .or_else(|| {
+                        metric_sets
+                            .iter()
+                            .find_map(|ms| ms.matching(reference).next())
+                            .and_then(|m| m.get_value())
+                    })
+                    .unwrap_or(f64::NAN)
+            };
+            let value = metric.expression.evaluate(&lookup);
+            values.push(value);
+        }
+
+        DerivedValues {
+            metrics: &self.metrics,
+            values,
+        }
+    }
+}
+
+pub struct DerivedValues<'a> {
+    metrics: &'a [DerivedMetric],
+    values: Vec<f64>,
+}
+
+impl DerivedValues<'_> {
+    /// Value of the first derived metric with the given name
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.metrics
+            .iter()
+            .position(|m| m.name == name)
+            .map(|index| self.values[index])
+    }
+}
+
+/// Prometheus text format, every family with its TYPE line directly before
+/// its samples, like `Exposition`. Metrics of the same name don't have to
+/// be defined next to each other.
+impl Display for DerivedValues<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut families: BTreeMap<&str, Vec<(&DerivedMetric, f64)>> = BTreeMap::new();
+        for (metric, value) in self.metrics.iter().zip(&self.values) {
+            families
+                .entry(metric.name.as_str())
+                .or_default()
+                .push((metric, *value));
+        }
+
+        for (name, metrics) in families {
+            writeln!(f, "# TYPE {name} gauge")?;
+            for (metric, value) in metrics {
+                let labels = metric
+                    .labels
+                    .iter()
+                    .map(|(key, value)| KV::new(key.clone(), value.clone()))
+                    .collect();
+                let base = BaseMetric::new(MetricType::Gauge, metric.name.clone(), labels, 0);
+                writeln!(f, "{} {}", base, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{
+        et,
+        planner::ReadBlock,
+        snapshot::{RUNNING_DATA_COUNT, RUNNING_DATA_START},
+    };
+
+    const CONFIG: &str = r#"
+        [[metric]]
+        name = "goodwe_east_roof_pv_watts"
+        labels = { roof = "east" }
+        expression = 'goodwe_power_pv_watts{mppt="pv1"} + goodwe_power_pv_watts{mppt="pv3"}'
+
+        [[metric]]
+        name = "goodwe_efficiency_ratio"
+        expression = '''
+            if(goodwe_east_roof_pv_watts > 0,
+               clamp(goodwe_active_power_total_watts / goodwe_east_roof_pv_watts, 0, 1),
+               0)
+        '''
+    "#;
+
+    fn parse(config: &str) -> DerivedMetrics {
+        toml::from_str(config).unwrap()
+    }
+
+    fn base_metrics(pv1: i32, pv3: i32, active_power: i16) -> MetricSet {
+        let mut registers = vec![0_u8; RUNNING_DATA_COUNT as usize * 2];
+        let mut put = |register: u16, bytes: &[u8]| {
+            let offset = (register - RUNNING_DATA_START) as usize * 2;
+            registers[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        put(35105, &pv1.to_be_bytes());
+        put(35113, &pv3.to_be_bytes());
+        put(35140, &active_power.to_be_bytes());
+
+        let mut ms = et::base_metrics();
+        ms.read_block(
+            &ReadBlock {
+                start: RUNNING_DATA_START,
+                count: RUNNING_DATA_COUNT,
+            },
+            &registers,
+        )
+        .unwrap();
+        ms
+    }
+
+    #[test]
+    fn evaluates_in_order() {
+        let derived = parse(CONFIG);
+        let metric_sets = [base_metrics(1500, 500, 1900)];
+        derived.validate(&metric_sets).unwrap();
+
+        let values = derived.evaluate(&metric_sets);
+        assert_eq!(values.get("goodwe_east_roof_pv_watts"), Some(2000.0));
+        assert_eq!(values.get("goodwe_efficiency_ratio"), Some(0.95));
+
+        let values = derived.evaluate(&[base_metrics(0, 0, 0)]);
+        assert_eq!(values.get("goodwe_efficiency_ratio"), Some(0.0));
+    }
+
+    #[test]
+    fn prometheus_output() {
+        let derived = parse(CONFIG);
+        let output = derived
+            .evaluate(&[base_metrics(1500, 500, 1000)])
+            .to_string();
+
+        assert_eq!(
+            output,
+            "# TYPE goodwe_east_roof_pv_watts gauge\n\
+             goodwe_east_roof_pv_watts {roof=\"east\", } 2000\n\
+             # TYPE goodwe_efficiency_ratio gauge\n\
+             goodwe_efficiency_ratio {} 0.5\n"
+        );
+    }
+
+    #[test]
+    fn prometheus_output_groups_families() {
+        let derived = parse(
+            r#"
+            [[metric]]
+            name = "goodwe_roof_pv_watts"
+            labels = { roof = "east" }
+            expression = 'goodwe_power_pv_watts{mppt="pv1"}'
+
+            [[metric]]
+            name = "goodwe_pv_total_watts"
+            expression = 'goodwe_power_pv_watts{mppt="pv1"} + goodwe_power_pv_watts{mppt="pv3"}'
+
+            [[metric]]
+            name = "goodwe_roof_pv_watts"
+            labels = { roof = "west" }
+            expression = 'goodwe_power_pv_watts{mppt="pv3"}'
+            "#,
+        );
+        let output = derived.evaluate(&[base_metrics(1500, 500, 0)]).to_string();
+
+        assert_eq!(
+            output,
+            "# TYPE goodwe_pv_total_watts gauge\n\
+             goodwe_pv_total_watts {} 2000\n\
+             # TYPE goodwe_roof_pv_watts gauge\n\
+             goodwe_roof_pv_watts {roof=\"east\", } 1500\n\
+             goodwe_roof_pv_watts {roof=\"west\", } 500\n"
+        );
+    }
+
+    #[test]
+    fn missing_values_are_nan() {
+        let derived = parse(CONFIG);
+        let values = derived.evaluate(&[et::base_metrics()]);
+        assert!(values.get("goodwe_east_roof_pv_watts").unwrap().is_nan());
+    }
+
+    #[test]
+    fn rejects_unknown_references() {
+        let metric_sets = et::all_metrics();
+        let error = |config| {
+            parse(config)
+                .validate(&metric_sets)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error("[[metric]]\nname = \"a\"\nexpression = 'goodwe_unknown + 1'"),
+            "Derived metric a refers to unknown metric goodwe_unknown"
+        );
+        assert_eq!(
+            error("[[metric]]\nname = \"a\"\nexpression = 'goodwe_power_pv_watts{mppt=\"pv9\"}'"),
+            "Derived metric a refers to unknown metric goodwe_power_pv_watts{mppt=\"pv9\"}"
+        );
+        // Only derived metrics defined before can be used
+        assert_eq!(
+            error("[[metric]]\nname = \"a\"\nexpression = 'b'\n[[metric]]\nname = \"b\"\nexpression = '1'"),
+            "Derived metric a refers to unknown metric b"
+        );
+    }
+
+    #[test]
+    fn rejects_ambiguous_references() {
+        let error = parse("[[metric]]\nname = \"a\"\nexpression = 'goodwe_power_pv_watts * 2'")
+            .validate(&et::all_metrics())
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            DerivedMetricError::AmbiguousReference { .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        let error = parse("[[metric]]\nname = \"east roof\"\nexpression = '1'")
+            .validate(&[])
+            .unwrap_err();
+        assert!(matches!(error, DerivedMetricError::InvalidName(_)));
+    }
+
+    #[test]
+    fn rejects_exported_names() {
+        let error = |name: &str| {
+            parse(&format!("[[metric]]\nname = \"{name}\"\nexpression = '1'"))
+                .validate(&et::all_metrics())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("goodwe_power_pv_watts"),
+            "Derived metric goodwe_power_pv_watts is already exported"
+        );
+        assert_eq!(
+            error("goodwe_autarky_ratio"),
+            "Derived metric goodwe_autarky_ratio is already exported"
+        );
+        assert_eq!(
+            error("goodwe_up"),
+            "Derived metric goodwe_up is already exported"
+        );
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let config = r#"
+            [[metric]]
+            name = "goodwe_roof_pv_watts"
+            labels = { roof = "east" }
+            expression = '1'
+
+            [[metric]]
+            name = "goodwe_roof_pv_watts"
+            labels = { roof = "west" }
+            expression = '2'
+        "#;
+        assert!(parse(config).validate(&[]).is_ok());
+
+        let config = config.replace("west", "east");
+        assert!(matches!(
+            parse(&config).validate(&[]),
+            Err(DerivedMetricError::Duplicate(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_label_values() {
+        for value in [r#"\"east\""#, r"east\\roof", r"east\nroof"] {
+            let config = format!(
+                "[[metric]]\nname = \"a\"\nlabels = {{ roof = \"{value}\" }}\nexpression = '1'"
+            );
+            assert!(
+                matches!(
+                    parse(&config).validate(&[]),
+                    Err(DerivedMetricError::InvalidLabelValue(_))
+                ),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        let error =
+            toml::from_str::<DerivedMetrics>("[[metric]]\nname = \"a\"\nexpression = '1 +'")
+                .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("invalid expression '1 +': unexpected end of expression at position 3"));
+    }
+}
@@ -0,0 +1,563 @@
+use super::definitions::{
+    Bitfield, Clock, ClockField, Current, Decimal, Energy, Flag, FloatEnergy, Frequency, Integer,
+    LargeEnergy, LargeInteger, LargePower, MetricSet, Percentage, Power, State, Temperature,
+    UnsignedPower, Voltage,
+};
+use crate::settings::work_mode::{WorkMode, REGISTER_WORK_MODE};
+
+const METRIC_INVERTER_CLOCK: &str = "inverter_clock";
+const METRIC_VOLTAGE_PV: &str = "voltage_pv_volts";
+const METRIC_CURRENT_PV: &str = "current_pv_amperes";
+const METRIC_POWER_PV: &str = "power_pv_watts";
+
+const METRIC_VOLTAGE_GRID: &str = "voltage_grid_volts";
+const METRIC_CURRENT_GRID: &str = "current_grid_amperes";
+const METRIC_POWER_GRID: &str = "power_grid_watts";
+const METRIC_FREQUENCY_GRID: &str = "frequency_grid_hertz";
+
+const METRIC_VOLTAGE_BACKUP: &str = "voltage_backup_volts";
+const METRIC_CURRENT_BACKUP: &str = "current_backup_amperes";
+const METRIC_POWER_BACKUP: &str = "power_backup_watts";
+const METRIC_FREQUENCY_BACKUP: &str = "frequency_backup_hertz";
+const METRIC_BACKUP_MODE: &str = "backup_mode";
+
+const METRIC_LOAD: &str = "load_watts";
+
+const METRIC_TEMP: &str = "temperature_celsius";
+
+const METRIC_INT_VOLTAGE: &str = "voltage_internal_volts";
+
+const METRIC_ACTIVE_POWER: &str = "active_power_watts";
+const METRIC_METER_ACTIVE_POWER: &str = "meter_active_power_watts";
+const METRIC_METER_REACTIVE_POWER: &str = "meter_reactive_power_var";
+const METRIC_METER_APPARENT_POWER: &str = "meter_apparent_power_va";
+
+const METRIC_VOLTAGE_METER: &str = "voltage_meter_volts";
+const METRIC_CURRENT_METER: &str = "current_meter_amperes";
+
+const METRIC_POWER_FACTOR: &str = "power_factor";
+
+const GRID_MODES: &[(u16, &str)] = &[(0, "Not connected"), (1, "Connected"), (2, "Fault")];
+
+const GRID_DIRECTIONS: &[(u16, &str)] = &[(0, "Idle"), (1, "Exporting"), (2, "Importing")];
+
+const BATTERY_MODES: &[(u16, &str)] = &[
+    (0, "No battery"),
+    (1, "Standby"),
+    (2, "Discharge"),
+    (3, "Charge"),
+    (4, "To be charged"),
+    (5, "To be discharged"),
+];
+
+const RUNNING_MODES: &[(u16, &str)] = &[
+    (0, "Wait"),
+    (1, "Normal (On-Grid)"),
+    (2, "Normal (Off-Grid)"),
+    (3, "Fault"),
+    (4, "Flash"),
+    (5, "Check"),
+];
+
+const METER_TEST_STATES: &[(u16, &str)] = &[
+    (0, "Not checked"),
+    (1, "Correct"),
+    (2, "Reverse"),
+    (3, "Incorrect"),
+];
+
+const METER_COMM_STATES: &[(u16, &str)] = &[(0, "NOK"), (1, "OK")];
+
+const METER_TYPES: &[(u16, &str)] = &[
+    (0, "Single Phase"),
+    (1, "3P3W"),
+    (2, "3P4W"),
+    (3, "HomeKit"),
+];
+
+/// Error flags of the inverter, 35189 holds the high word
+const INVERTER_ERRORS: &[Flag] = &[
+    (0, "gfci_device_check_failure", "GFCI Device Check Failure"),
+    (
+        1,
+        "internal_communication_failure",
+        "Internal Communication Failure",
+    ),
+    (2, "ac_hct_failure", "AC HCT Failure"),
+    (3, "dc_bus_high", "DC Bus High"),
+    (4, "dc_spi_failure", "DC SPI Failure"),
+    (5, "ground_current_failure", "Ground I Failure"),
+    (6, "gfci_device_failure", "GFCI Device Failure"),
+    (7, "relay_check_failure", "Relay Check Failure"),
+    (8, "achct_failure", "ACHCT Failure"),
+    (9, "utility_loss", "Utility Loss"),
+    (10, "ground_current_failure_2", "Ground I Failure"),
+    (11, "dc_bus_high_2", "DC Bus High"),
+    (12, "internal_fan_failure", "Internal Fan Failure"),
+    (13, "over_temperature", "Over Temperature"),
+    (14, "utility_loss_2", "Utility Loss"),
+    (15, "pv_over_voltage", "PV Over Voltage"),
+    (16, "external_fan_failure", "External Fan Failure"),
+    (17, "vac_failure", "Vac Failure"),
+    (18, "isolation_failure", "Isolation Failure"),
+    (19, "dc_injection_high", "DC Injection High"),
+    (20, "backup_over_load", "Back-Up Over Load"),
+    (22, "fac_consistency_failure", "Fac Consistency Failure"),
+    (23, "vac_consistency_failure", "Vac Consistency Failure"),
+    (25, "relay_check_failure_2", "Relay Check Failure"),
+    (27, "phase_angle_failure", "Phase Angle Failure"),
+    (28, "dsp_communication_failure", "DSP Communication Failure"),
+    (29, "fac_failure", "Fac Failure"),
+    (30, "eeprom_failure", "EEPROM R/W Failure"),
+    (
+        31,
+        "internal_communication_failure_2",
+        "Internal Communication Failure",
+    ),
+];
+
+/// Alarms of the BMS in the low word at 37006, the meaning of the high word
+/// at 37011 is not known
+const BMS_ERRORS: &[Flag] = &[
+    (0, "charge_over_voltage_2", "Charging over-voltage 2"),
+    (
+        1,
+        "discharge_under_voltage_2",
+        "Discharging under-voltage 2",
+    ),
+    (2, "cell_temperature_high_2", "Cell temperature high 2"),
+    (3, "cell_temperature_low_2", "Cell temperature low 2"),
+    (4, "charge_over_current_2", "Charging over-current 2"),
+    (5, "discharge_over_current_2", "Discharging over-current 2"),
+    (6, "precharge_fault", "Precharge fault"),
+    (7, "dc_bus_fault", "DC bus fault"),
+    (8, "battery_break", "Battery break"),
+    (9, "battery_lock", "Battery lock"),
+    (
+        10,
+        "discharge_circuit_failure",
+        "Discharging circuit failure",
+    ),
+    (11, "charge_circuit_failure", "Charging circuit failure"),
+    (12, "communication_failure_2", "Communication failure 2"),
+    (13, "cell_temperature_high_3", "Cell temperature high 3"),
+    (
+        14,
+        "discharge_under_voltage_3",
+        "Discharging under-voltage 3",
+    ),
+    (15, "charge_over_voltage_3", "Charging over-voltage 3"),
+];
+
+/// Warnings of the BMS in the low word at 37010, the meaning of the high
+/// word at 37013 is not known
+const BMS_WARNINGS: &[Flag] = &[
+    (0, "charge_over_voltage_1", "Charging over-voltage 1"),
+    (
+        1,
+        "discharge_under_voltage_1",
+        "Discharging under-voltage 1",
+    ),
+    (2, "cell_temperature_high_1", "Cell temperature high 1"),
+    (3, "cell_temperature_low_1", "Cell temperature low 1"),
+    (4, "charge_over_current_1", "Charging over-current 1"),
+    (5, "discharge_over_current_1", "Discharging over-current 1"),
+    (6, "communication_failure_1", "Communication failure 1"),
+    (7, "system_reboot", "System reboot"),
+    (8, "cell_imbalance", "Cell imbalance"),
+    (9, "system_temperature_low_1", "System temperature low 1"),
+    (10, "system_temperature_low_2", "System temperature low 2"),
+    (11, "system_temperature_high", "System temperature high"),
+];
+
+/// Diagnostic status bits, 35220 holds the high word. They mostly explain
+/// why the inverter doesn't charge or discharge the battery.
+const DIAGNOSTIC_STATUS: &[Flag] = &[
+    (0, "battery_voltage_low", "Battery voltage low"),
+    (1, "battery_soc_low", "Battery SOC low"),
+    (2, "battery_soc_in_back", "Battery SOC in back"),
+    (3, "bms_discharge_disabled", "BMS: Discharge disabled"),
+    (4, "discharge_time_on", "Discharge time on"),
+    (5, "charge_time_on", "Charge time on"),
+    (6, "discharge_driver_on", "Discharge driver on"),
+    (7, "bms_discharge_current_low", "BMS: Discharge current low"),
+    (
+        8,
+        "app_discharge_current_low",
+        "APP: Discharge current too low",
+    ),
+    (
+        9,
+        "meter_communication_failure",
+        "Meter communication failure",
+    ),
+    (10, "meter_connection_reversed", "Meter connection reversed"),
+    (11, "self_use_load_light", "Self-use load light"),
+    (
+        12,
+        "ems_discharge_current_zero",
+        "EMS: Discharge current is zero",
+    ),
+    (
+        13,
+        "discharge_bus_high_pv_voltage",
+        "Discharge BUS high PV voltage",
+    ),
+    (14, "battery_disconnected", "Battery disconnected"),
+    (15, "battery_overcharged", "Battery overcharged"),
+    (16, "bms_temperature_high", "BMS: Temperature too high"),
+    (17, "bms_charge_too_high", "BMS: Charge too high"),
+    (18, "bms_charge_disabled", "BMS: Charge disabled"),
+    (19, "self_use_off", "Self-use off"),
+    (20, "soc_delta_volatile", "SOC delta too volatile"),
+    (
+        21,
+        "battery_self_discharge_high",
+        "Battery self discharge too high",
+    ),
+    (22, "battery_soc_low_off_grid", "Battery SOC low (off-grid)"),
+    (23, "grid_wave_unstable", "Grid wave unstable"),
+    (24, "export_power_limit_set", "Export power limit set"),
+    (25, "pf_value_set", "PF value set"),
+    (26, "real_power_limit_set", "Real power limit set"),
+    (27, "dc_output_on", "DC output on"),
+    (28, "soc_protect_off", "SOC protect off"),
+    (29, "bms_emergency_charging", "BMS: Emergency charging"),
+];
+
+pub fn all_metrics() -> Vec<MetricSet> {
+    vec![
+        base_metrics(),
+        battery_metrics(),
+        meter_metrics(),
+        settings_metrics(),
+    ]
+}
+
+/// Running data, 35100 to 35221 with the PV total power following at 35301.
+/// Powers and counters spanning two words are signed and unsigned
+/// respectively, high word first. The grid, backup and load powers are
+/// single words, the register before each of them is reserved.
+pub fn base_metrics() -> MetricSet {
+    let metrics = vec![
+        Clock::easy(35100, ClockField::Year, METRIC_INVERTER_CLOCK),
+        Clock::easy(35100, ClockField::Month, METRIC_INVERTER_CLOCK),
+        Clock::easy(35100, ClockField::Day, METRIC_INVERTER_CLOCK),
+        Clock::easy(35100, ClockField::Hour, METRIC_INVERTER_CLOCK),
+        Clock::easy(35100, ClockField::Minute, METRIC_INVERTER_CLOCK),
+        Clock::easy(35100, ClockField::Second, METRIC_INVERTER_CLOCK),
+        Voltage::easy(35103, METRIC_VOLTAGE_PV, "mppt", "pv1"),
+        Current::easy(35104, METRIC_CURRENT_PV, "mppt", "pv1"),
+        LargePower::easy(35105, METRIC_POWER_PV, "mppt", "pv1"),
+        Voltage::easy(35107, METRIC_VOLTAGE_PV, "mppt", "pv2"),
+        Current::easy(35108, METRIC_CURRENT_PV, "mppt", "pv2"),
+        LargePower::easy(35109, METRIC_POWER_PV, "mppt", "pv2"),
+        Voltage::easy(35111, METRIC_VOLTAGE_PV, "mppt", "pv3"),
+        Current::easy(35112, METRIC_CURRENT_PV, "mppt", "pv3"),
+        LargePower::easy(35113, METRIC_POWER_PV, "mppt", "pv3"),
+        Voltage::easy(35115, METRIC_VOLTAGE_PV, "mppt", "pv4"),
+        Current::easy(35116, METRIC_CURRENT_PV, "mppt", "pv4"),
+        LargePower::easy(35117, METRIC_POWER_PV, "mppt", "pv4"),
+        Voltage::easy(35121, METRIC_VOLTAGE_GRID, "phase", "L1"),
+        Current::easy(35122, METRIC_CURRENT_GRID, "phase", "L1"),
+        Frequency::easy(35123, METRIC_FREQUENCY_GRID, "phase", "L1"),
+        Power::easy(35125, METRIC_POWER_GRID, "phase", "L1"),
+        Voltage::easy(35126, METRIC_VOLTAGE_GRID, "phase", "L2"),
+        Current::easy(35127, METRIC_CURRENT_GRID, "phase", "L2"),
+        Frequency::easy(35128, METRIC_FREQUENCY_GRID, "phase", "L2"),
+        Power::easy(35130, METRIC_POWER_GRID, "phase", "L2"),
+        Voltage::easy(35131, METRIC_VOLTAGE_GRID, "phase", "L3"),
+        Current::easy(35132, METRIC_CURRENT_GRID, "phase", "L3"),
+        Frequency::easy(35133, METRIC_FREQUENCY_GRID, "phase", "L3"),
+        Power::easy(35135, METRIC_POWER_GRID, "phase", "L3"),
+        State::easy(35136, "grid_mode", GRID_MODES),
+        // TODO: Remove the redundant labels
+        Power::easy(35138, "inverter_power_total_watts", "none", "none"),
+        Power::easy(35140, "active_power_total_watts", "none", "none"),
+        // The inverter has no register for it, it follows the active power
+        State::power_direction(35140, "grid_direction", GRID_DIRECTIONS),
+        Power::easy(35142, "inverter_reactive_power_var", "none", "none"),
+        Power::easy(35144, "inverter_apparent_power_va", "none", "none"),
+        Voltage::easy(35145, METRIC_VOLTAGE_BACKUP, "phase", "L1"),
+        Current::easy(35146, METRIC_CURRENT_BACKUP, "phase", "L1"),
+        Frequency::easy(35147, METRIC_FREQUENCY_BACKUP, "phase", "L1"),
+        // The backup output modes are not documented, so the three phases
+        // report the raw code
+        Integer::gauge(35148, METRIC_BACKUP_MODE, "phase", "L1"),
+        Power::easy(35150, METRIC_POWER_BACKUP, "phase", "L1"),
+        Voltage::easy(35151, METRIC_VOLTAGE_BACKUP, "phase", "L2"),
+        Current::easy(35152, METRIC_CURRENT_BACKUP, "phase", "L2"),
+        Frequency::easy(35153, METRIC_FREQUENCY_BACKUP, "phase", "L2"),
+        Integer::gauge(35154, METRIC_BACKUP_MODE, "phase", "L2"),
+        Power::easy(35156, METRIC_POWER_BACKUP, "phase", "L2"),
+        Voltage::easy(35157, METRIC_VOLTAGE_BACKUP, "phase", "L3"),
+        Current::easy(35158, METRIC_CURRENT_BACKUP, "phase", "L3"),
+        Frequency::easy(35159, METRIC_FREQUENCY_BACKUP, "phase", "L3"),
+        Integer::gauge(35160, METRIC_BACKUP_MODE, "phase", "L3"),
+        Power::easy(35162, METRIC_POWER_BACKUP, "phase", "L3"),
+        Power::easy(35164, METRIC_LOAD, "phase", "L1"),
+        Power::easy(35166, METRIC_LOAD, "phase", "L2"),
+        Power::easy(35168, METRIC_LOAD, "phase", "L3"),
+        Power::easy(35170, METRIC_LOAD, "type", "Backup"),
+        Power::easy(35172, METRIC_LOAD, "type", "Total"),
+        Percentage::easy(35173, "backup_utilization_ratio", "none", "none"),
+        Temperature::easy(35174, METRIC_TEMP, "sensor", "Air"),
+        Temperature::easy(35175, METRIC_TEMP, "sensor", "Module"),
+        Temperature::easy(35176, METRIC_TEMP, "sensor", "Radiator"),
+        Integer::gauge(35177, "function_bits", "none", "none"),
+        Voltage::easy(35178, METRIC_INT_VOLTAGE, "sensor", "Bus"),
+        Voltage::easy(35179, METRIC_INT_VOLTAGE, "sensor", "NBus"),
+        Voltage::easy(35180, "voltage_battery_volts", "none", "none"),
+        Current::easy(35181, "current_battery_volts", "string", "Battery"),
+        LargePower::easy(35182, "power_battery_watts", "none", "none"),
+        State::easy(35184, "battery_mode", BATTERY_MODES),
+        Integer::gauge(35185, "warning_code", "none", "none"),
+        // Index into the inverter's table of grid codes. The table runs to
+        // dozens of entries, as a state set nearly all samples would be zero
+        // and none of the names is confirmed on hardware
+        Integer::gauge(35186, "safety_country", "none", "none"),
+        State::easy(35187, "running_mode", RUNNING_MODES),
+        // Not the work mode at 47000, and its codes are not documented
+        Integer::gauge(35188, "operation_mode", "none", "none"),
+        Bitfield::easy(&[35190, 35189], "inverter_error", INVERTER_ERRORS),
+        LargeEnergy::easy(35191, "pv_generation_total", "timeframe", "all"),
+        LargeEnergy::easy(35193, "pv_generation_total", "timeframe", "today"),
+        LargeEnergy::easy(35195, "pv_export_total", "timeframe", "all"),
+        LargeInteger::easy(35197, "operation_hours_total", "none", "none"),
+        Energy::easy(35199, "pv_export_total", "timeframe", "today"),
+        LargeEnergy::easy(35200, "energy_import_total", "timeframe", "all"),
+        Energy::easy(35202, "energy_import_total", "timeframe", "today"),
+        LargeEnergy::easy(35203, "energy_load_total", "timeframe", "all"),
+        Energy::easy(35205, "energy_load_total", "timeframe", "today"),
+        LargeEnergy::easy(35206, "energy_battery_charge_total", "timeframe", "all"),
+        Energy::easy(35208, "energy_battery_charge_total", "timeframe", "today"),
+        LargeEnergy::easy(35209, "energy_battery_discharge_total", "timeframe", "all"),
+        Energy::easy(
+            35211,
+            "energy_battery_discharge_total",
+            "timeframe",
+            "today",
+        ),
+        Bitfield::easy(&[35221, 35220], "diagnostic_status", DIAGNOSTIC_STATUS),
+        LargePower::easy(35301, "power_pv_total_watts", "none", "none"),
+    ];
+
+    MetricSet::new(metrics)
+}
+
+pub fn battery_metrics() -> MetricSet {
+    let metrics = vec![
+        Integer::easy(37000, "battery_bms", "none", "none"),
+        Integer::easy(37001, "battery_index", "none", "none"),
+        // No value table is published for the BMS status, so unlike the
+        // other status registers it stays the raw code
+        Integer::gauge(37002, "battery_status", "none", "none"),
+        Temperature::easy(37003, METRIC_TEMP, "sensor", "Battery"),
+        Integer::easy(37004, "battery_current_limit_amperes", "type", "Charge"),
+        Integer::easy(37005, "battery_current_limit_amperes", "type", "Discharge"),
+        Bitfield::easy(&[37006, 37011], "battery_error", BMS_ERRORS),
+        Percentage::easy(37007, "battery_state_ratio", "type", "State of Charge"),
+        Percentage::easy(37008, "battery_state_ratio", "type", "State of Health"),
+        Integer::easy(37009, "battery_modules", "none", "none"),
+        Bitfield::easy(&[37010, 37013], "battery_warning", BMS_WARNINGS),
+        Integer::easy(37014, "battery_version", "part", "SW"),
+        Integer::easy(37015, "battery_version", "part", "HW"),
+        Integer::easy(37016, "battery_cell_temp_id", "type", "Max"),
+        Integer::easy(37017, "battery_cell_temp_id", "type", "Min"),
+        Integer::easy(37018, "battery_cell_voltage_id", "type", "Max"),
+        Integer::easy(37019, "battery_cell_voltage_id", "type", "Min"),
+        Temperature::easy(37020, "battery_cell_temp_celsius", "type", "Max"),
+        Temperature::easy(37021, "battery_cell_temp_celsius", "type", "Min"),
+        Voltage::easy(37022, "battery_cell_voltage_volts", "type", "Max"),
+        Voltage::easy(37023, "battery_cell_voltage_volts", "type", "Min"),
+    ];
+
+    MetricSet::new(metrics)
+}
+
+pub fn meter_metrics() -> MetricSet {
+    let metrics = vec![
+        // Raw code as well, the communication modes are not documented
+        Integer::gauge(36000, "commode", "none", "none"),
+        Integer::easy(36001, "rssi", "none", "none"),
+        Integer::easy(36002, "manufacture_code", "none", "none"),
+        State::easy(36003, "meter_test_status", METER_TEST_STATES),
+        State::easy(36004, "meter_comm_status", METER_COMM_STATES),
+        Power::easy(36005, METRIC_ACTIVE_POWER, "phase", "L1"),
+        Power::easy(36006, METRIC_ACTIVE_POWER, "phase", "L2"),
+        Power::easy(36007, METRIC_ACTIVE_POWER, "phase", "L3"),
+        Power::easy(36008, METRIC_ACTIVE_POWER, "phase", "all"),
+        Power::easy(36009, "reactive_power_total_var", "phase", "all"),
+        Decimal::easy(36010, METRIC_POWER_FACTOR, "phase", "L1"),
+        Decimal::easy(36011, METRIC_POWER_FACTOR, "phase", "L2"),
+        Decimal::easy(36012, METRIC_POWER_FACTOR, "phase", "L3"),
+        Decimal::easy(36013, METRIC_POWER_FACTOR, "phase", "all"),
+        Frequency::easy(36014, "meter_frequency_hertz", "none", "none"),
+        FloatEnergy::easy(36015, "meter_energy_total_kwh", "type", "export"),
+        FloatEnergy::easy(36017, "meter_energy_total_kwh", "type", "import"),
+        LargePower::easy(36019, METRIC_METER_ACTIVE_POWER, "phase", "L1"),
+        LargePower::easy(36021, METRIC_METER_ACTIVE_POWER, "phase", "L2"),
+        LargePower::easy(36023, METRIC_METER_ACTIVE_POWER, "phase", "L3"),
+        LargePower::easy(36025, METRIC_METER_ACTIVE_POWER, "phase", "all"),
+        LargePower::easy(36027, METRIC_METER_REACTIVE_POWER, "phase", "L1"),
+        LargePower::easy(36029, METRIC_METER_REACTIVE_POWER, "phase", "L2"),
+        LargePower::easy(36031, METRIC_METER_REACTIVE_POWER, "phase", "L3"),
+        LargePower::easy(36033, METRIC_METER_REACTIVE_POWER, "phase", "all"),
+        LargePower::easy(36035, METRIC_METER_APPARENT_POWER, "phase", "L1"),
+        LargePower::easy(36037, METRIC_METER_APPARENT_POWER, "phase", "L2"),
+        LargePower::easy(36039, METRIC_METER_APPARENT_POWER, "phase", "L3"),
+        LargePower::easy(36041, METRIC_METER_APPARENT_POWER, "phase", "all"),
+        State::easy(36043, "meter_type", METER_TYPES),
+        Integer::easy(36044, "meter_sw_version", "none", "none"),
+        Voltage::easy(36052, METRIC_VOLTAGE_METER, "phase", "L1"),
+        Voltage::easy(36053, METRIC_VOLTAGE_METER, "phase", "L2"),
+        Voltage::easy(36054, METRIC_VOLTAGE_METER, "phase", "L3"),
+        Current::easy(36055, METRIC_CURRENT_METER, "phase", "L1"),
+        Current::easy(36056, METRIC_CURRENT_METER, "phase", "L2"),
+        Current::easy(36057, METRIC_CURRENT_METER, "phase", "L3"),
+    ];
+
+    MetricSet::new(metrics)
+}
+
+pub fn settings_metrics() -> MetricSet {
+    let work_modes: Vec<(u16, &str)> = WorkMode::ALL
+        .iter()
+        .map(|mode| (*mode as u16, mode.name()))
+        .collect();
+
+    let metrics = vec![
+        State::easy(REGISTER_WORK_MODE, "work_mode", &work_modes),
+        // 1: export limit enabled, 0: disabled
+        Integer::gauge(47509, "export_limit_enabled", "none", "none"),
+        UnsignedPower::easy(47510, "export_limit_watts", "none", "none"),
+    ];
+
+    MetricSet::new(metrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{
+        modbus::{self, DEFAULT_ADDR},
+        planner::ReadBlock,
+        snapshot::{RunningData, RUNNING_DATA_COUNT, RUNNING_DATA_START},
+    };
+
+    /// Answer to reading the running data block, generated by the simulator
+    /// from the GW20K-ET image. It is not a capture from real hardware, so
+    /// these tests only show the map agrees with the simulator, which is
+    /// built from the same register documentation. Checking the map against
+    /// a real GW20K-ET is still open, replace this frame with a capture once
+    /// one is available.
+    const RUNNING_DATA_FRAME: &str = include_str!("gw20k-et-running-data.hex");
+
+    fn running_data() -> Vec<u8> {
+        let hex: String = RUNNING_DATA_FRAME.split_whitespace().collect();
+        let frame: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        modbus::get_payload(&frame, DEFAULT_ADDR, RUNNING_DATA_COUNT).unwrap()
+    }
+
+    fn read_base_metrics() -> MetricSet {
+        let mut ms = base_metrics();
+        ms.read_block(
+            &ReadBlock {
+                start: RUNNING_DATA_START,
+                count: RUNNING_DATA_COUNT,
+            },
+            &running_data(),
+        )
+        .unwrap();
+        ms
+    }
+
+    #[test]
+    fn running_data_frame_values() {
+        let ms = read_base_metrics();
+        let value = |name: &str, key, label| ms.value(&format!("goodwe_{name}"), key, label);
+
+        assert_eq!(value(METRIC_POWER_GRID, "phase", "L2"), Some(1510.0));
+        assert_eq!(
+            value("active_power_total_watts", "none", "none"),
+            Some(1843.0)
+        );
+        assert_eq!(
+            value("inverter_apparent_power_va", "none", "none"),
+            Some(4570.0)
+        );
+        assert_eq!(value(METRIC_LOAD, "phase", "L3"), Some(911.0));
+        assert_eq!(value("power_battery_watts", "none", "none"), Some(-2083.0));
+        assert_eq!(value("safety_country", "none", "none"), Some(2.0));
+        assert_eq!(value("operation_hours_total", "none", "none"), Some(8821.0));
+        assert_eq!(
+            value("pv_generation_total", "timeframe", "all"),
+            Some(18452.0)
+        );
+        assert_eq!(
+            value("energy_import_total", "timeframe", "all"),
+            Some(4128.0)
+        );
+        assert_eq!(
+            value("energy_battery_charge_total", "timeframe", "today"),
+            Some(8.8_f32 as f64)
+        );
+
+        let output = ms.to_string();
+        // 2024-06-21 12:30:00
+        assert!(output.contains("goodwe_inverter_clock {part=\"year\", } 2024\n"));
+        assert!(output.contains("goodwe_inverter_clock {part=\"month\", } 6\n"));
+        assert!(output.contains("goodwe_inverter_clock {part=\"day\", } 21\n"));
+        assert!(output.contains("goodwe_inverter_clock {part=\"hour\", } 12\n"));
+        assert!(output.contains("goodwe_inverter_clock {part=\"minute\", } 30\n"));
+        assert!(output.contains("goodwe_inverter_clock {part=\"second\", } 0\n"));
+        assert!(output.contains("goodwe_grid_direction {state=\"Exporting\", } 1\n"));
+        // Codes, not counts
+        assert!(output.contains("# TYPE goodwe_safety_country gauge\n"));
+        assert!(output.contains("# TYPE goodwe_warning_code gauge\n"));
+        assert!(output.contains(
+            "goodwe_diagnostic_status {flag=\"charge_time_on\", description=\"Charge time on\", } 1\n"
+        ));
+        // Outside of the block
+        assert_eq!(value("power_pv_total_watts", "none", "none"), None);
+    }
+
+    #[test]
+    fn running_data_frame_snapshot() {
+        let running = RunningData::decode(&running_data()).unwrap();
+
+        assert_eq!(running.grid[1].power, 1510);
+        assert_eq!(running.active_power, 1843);
+        assert_eq!(running.load.phases, [890, 760, 911]);
+        assert_eq!(running.operation_hours, 8821);
+        assert_eq!(running.energy.load_total, 10284.0);
+        assert_eq!(running.diagnostic_status, 0x30);
+    }
+
+    #[test]
+    fn grid_direction_follows_active_power() {
+        let direction = |power: i16| {
+            let mut ms = MetricSet::new(vec![State::power_direction(
+                35140,
+                "grid_direction",
+                GRID_DIRECTIONS,
+            )]);
+            ms.read_block(
+                &ReadBlock {
+                    start: 35140,
+                    count: 1,
+                },
+                &power.to_be_bytes(),
+            )
+            .unwrap();
+            ms.metrics[0].get_value()
+        };
+
+        assert_eq!(direction(1843), Some(1.0));
+        assert_eq!(direction(-400), Some(2.0));
+        assert_eq!(direction(-89), Some(0.0));
+    }
+}
@@ -0,0 +1,581 @@
+//! Arithmetic over metric values, used for derived metrics defined in the
+//! configuration.
+//!
+//! ```text
+//! expression := comparison
+//! comparison := sum (("<" | "<=" | ">" | ">=" | "==" | "!=") sum)?
+//! sum        := product (("+" | "-") product)*
+//! product    := unary (("*" | "/") unary)*
+//! unary      := "-" unary | primary
+//! primary    := number | reference | function "(" expression ("," expression)* ")"
+//!             | "(" expression ")"
+//! reference  := name ("{" label "=" "\"" value "\"" ("," label "=" "\"" value "\"")* "}")?
+//! ```
+//!
+//! Comparisons give 1 or 0, or NaN if either side is NaN. The functions are `min`, `max`, `abs`,
+//! `clamp(value, low, high)` and `if(condition, then, else)`.
+
+use std::{error::Error, fmt::Display};
+
+use serde::Deserialize;
+
+/// A metric selected by its name and some or all of its labels
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.labels.is_empty() {
+            let labels: Vec<String> = self
+                .labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{value}\""))
+                .collect();
+            write!(f, "{{{}}}", labels.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Operator {
+    fn apply(&self, left: f64, right: f64) -> f64 {
+        // A missing value must not turn into a valid condition
+        let truth = |value: bool| {
+            if left.is_nan() || right.is_nan() {
+                f64::NAN
+            } else if value {
+                1.0
+            } else {
+                0.0
+            }
+        };
+        match self {
+            Operator::Add => left + right,
+            Operator::Subtract => left - right,
+            Operator::Multiply => left * right,
+            Operator::Divide => left / right,
+            Operator::Less => truth(left < right),
+            Operator::LessOrEqual => truth(left <= right),
+            Operator::Greater => truth(left > right),
+            Operator::GreaterOrEqual => truth(left >= right),
+            Operator::Equal => truth(left == right),
+            Operator::NotEqual => truth(left != right),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Function {
+    Min,
+    Max,
+    Abs,
+    Clamp,
+    If,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            "abs" => Some(Function::Abs),
+            "clamp" => Some(Function::Clamp),
+            "if" => Some(Function::If),
+            _ => None,
+        }
+    }
+
+    /// Whether the function can be called with `count` arguments
+    fn accepts(&self, count: usize) -> bool {
+        match self {
+            Function::Min | Function::Max => count >= 1,
+            Function::Abs => count == 1,
+            Function::Clamp | Function::If => count == 3,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub enum Expression {
+    Number(f64),
+    Reference(Reference),
+    Negate(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    Call(Function, Vec<Expression>),
+}
+
+impl Expression {
+    pub fn parse(input: &str) -> Result<Self, ExpressionError> {
+        let mut parser = Parser {
+            input,
+            position: 0,
+            depth: 0,
+        };
+        let expression = parser.comparison()?;
+        parser.skip_whitespace();
+        if parser.position < input.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(expression)
+    }
+
+    /// All metrics the expression refers to, in order of appearance
+    pub fn references(&self) -> Vec<&Reference> {
+        match self {
+            Expression::Number(_) => vec![],
+            Expression::Reference(reference) => vec![reference],
+            Expression::Negate(inner) => inner.references(),
+            Expression::Binary(_, left, right) => {
+                let mut references = left.references();
+                references.extend(right.references());
+                references
+            }
+            Expression::Call(_, arguments) => {
+                arguments.iter().flat_map(|a| a.references()).collect()
+            }
+        }
+    }
+
+    /// Compute the value, looking up the values of referenced metrics with
+    /// `value`. Missing values should be given as NaN, which then usually
+    /// makes the whole result NaN.
+    pub fn evaluate(&self, value: &impl Fn(&Reference) -> f64) -> f64 {
+        match self {
+            Expression::Number(number) => *number,
+            Expression::Reference(reference) => value(reference),
+            Expression::Negate(inner) => -inner.evaluate(value),
+            Expression::Binary(operator, left, right) => {
+                operator.apply(left.evaluate(value), right.evaluate(value))
+            }
+            Expression::Call(function, arguments) => {
+                let mut values = arguments.iter().map(|a| a.evaluate(value));
+                match function {
+                    // f64::min would silently skip NaN, missing values have to show
+                    Function::Min => values
+                        .reduce(|a, b| {
+                            if a.is_nan() || b.is_nan() {
+                                f64::NAN
+                            } else {
+                                a.min(b)
+                            }
+                        })
+                        .unwrap_or(f64::NAN),
+                    Function::Max => values
+                        .reduce(|a, b| {
+                            if a.is_nan() || b.is_nan() {
+                                f64::NAN
+                            } else {
+                                a.max(b)
+                            }
+                        })
+                        .unwrap_or(f64::NAN),
+                    Function::Abs => values.next().unwrap_or(f64::NAN).abs(),
+                    Function::Clamp => {
+                        let (x, low, high) = (
+                            values.next().unwrap_or(f64::NAN),
+                            values.next().unwrap_or(f64::NAN),
+                            values.next().unwrap_or(f64::NAN),
+                        );
+                        if x.is_nan() || low.is_nan() || high.is_nan() {
+                            f64::NAN
+                        } else {
+                            x.max(low).min(high)
+                        }
+                    }
+                    // Only the chosen branch is evaluated
+                    Function::If => {
+                        let condition = arguments[0].evaluate(value);
+                        if condition.is_nan() {
+                            f64::NAN
+                        } else if condition != 0.0 {
+                            arguments[1].evaluate(value)
+                        } else {
+                            arguments[2].evaluate(value)
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Expression::parse(&value).map_err(|e| format!("invalid expression '{value}': {e}"))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ExpressionError {
+    /// Byte offset into the expression
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for ExpressionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl Error for ExpressionError {}
+
+/// Deepest nesting of operators, parentheses and calls. Parsing, evaluating
+/// and dropping expressions recurse, deeper ones could overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+    /// Nesting of the expression parsed at the moment
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ExpressionError {
+        ExpressionError {
+            position: self.position,
+            message: message.to_owned(),
+        }
+    }
+
+    /// Parse one level deeper. Errors end the whole parse, so the depth
+    /// only has to be restored on success.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ExpressionError>,
+    ) -> Result<T, ExpressionError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("expression is nested too deeply"));
+        }
+        let result = parse(self)?;
+        self.depth -= 1;
+        Ok(result)
+    }
+
+    fn rest(&self) -> &str {
+        &self.input[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consume `token` if the input continues with it
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), ExpressionError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{token}'")))
+        }
+    }
+
+    /// Consume characters as long as `accept` is true for them
+    fn take_while(&mut self, accept: impl Fn(char) -> bool) -> &str {
+        let start = self.position;
+        let length = self
+            .rest()
+            .find(|c| !accept(c))
+            .unwrap_or(self.rest().len());
+        self.position += length;
+        &self.input[start..self.position]
+    }
+
+    fn comparison(&mut self) -> Result<Expression, ExpressionError> {
+        let left = self.sum()?;
+        // Longer tokens first, so "<=" is not taken for "<"
+        let operators = [
+            ("<=", Operator::LessOrEqual),
+            (">=", Operator::GreaterOrEqual),
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<", Operator::Less),
+            (">", Operator::Greater),
+        ];
+        for (token, operator) in operators {
+            if self.eat(token) {
+                let right = self.nested(Self::sum)?;
+                return Ok(Expression::Binary(
+                    operator,
+                    Box::new(left),
+                    Box::new(right),
+                ));
+            }
+        }
+        Ok(left)
+    }
+
+    /// Every operator of a chain like `a + b + c` nests the expression one
+    /// level deeper
+    fn sum(&mut self) -> Result<Expression, ExpressionError> {
+        let depth = self.depth;
+        let mut left = self.product()?;
+        loop {
+            let operator = if self.eat("+") {
+                Operator::Add
+            } else if self.eat("-") {
+                Operator::Subtract
+            } else {
+                self.depth = depth;
+                return Ok(left);
+            };
+            let right = self.nested(Self::product)?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+            self.depth += 1;
+        }
+    }
+
+    fn product(&mut self) -> Result<Expression, ExpressionError> {
+        let depth = self.depth;
+        let mut left = self.unary()?;
+        loop {
+            let operator = if self.eat("*") {
+                Operator::Multiply
+            } else if self.eat("/") {
+                Operator::Divide
+            } else {
+                self.depth = depth;
+                return Ok(left);
+            };
+            let right = self.nested(Self::unary)?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+            self.depth += 1;
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        if self.eat("-") {
+            return Ok(Expression::Negate(Box::new(self.nested(Self::unary)?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expression, ExpressionError> {
+        if self.eat("(") {
+            let inner = self.nested(Self::comparison)?;
+            self.expect(")")?;
+            return Ok(inner);
+        }
+
+        self.skip_whitespace();
+        let start = self.position;
+        match self.rest().chars().next() {
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let number = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                number.parse().map(Expression::Number).map_err(|_| {
+                    self.position = start;
+                    self.error("invalid number")
+                })
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let name = self
+                    .take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+                    .to_owned();
+                if self.eat("(") {
+                    self.call(&name, start)
+                } else {
+                    self.reference(name)
+                }
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+
+    fn call(&mut self, name: &str, start: usize) -> Result<Expression, ExpressionError> {
+        let function = Function::from_name(name).ok_or_else(|| ExpressionError {
+            position: start,
+            message: format!("unknown function '{name}'"),
+        })?;
+
+        let mut arguments = vec![self.nested(Self::comparison)?];
+        while self.eat(",") {
+            arguments.push(self.nested(Self::comparison)?);
+        }
+        self.expect(")")?;
+
+        if !function.accepts(arguments.len()) {
+            return Err(ExpressionError {
+                position: start,
+                message: format!("wrong number of arguments for '{name}'"),
+            });
+        }
+        Ok(Expression::Call(function, arguments))
+    }
+
+    fn reference(&mut self, name: String) -> Result<Expression, ExpressionError> {
+        let mut labels = Vec::new();
+        if self.eat("{") {
+            loop {
+                self.skip_whitespace();
+                let key = self
+                    .take_while(|c| c.is_ascii_alphanumeric() || c == '_')
+                    .to_owned();
+                if key.is_empty() {
+                    return Err(self.error("expected label name"));
+                }
+                self.expect("=")?;
+                self.expect("\"")?;
+                let value = self.take_while(|c| c != '"').to_owned();
+                self.expect("\"")?;
+                labels.push((key, value));
+
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect("}")?;
+        }
+
+        Ok(Expression::Reference(Reference { name, labels }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(input: &str) -> f64 {
+        Expression::parse(input)
+            .unwrap()
+            .evaluate(
+                &|reference| match (reference.name.as_str(), reference.labels.first()) {
+                    ("pv", Some((_, value))) if value == "pv1" => 1200.0,
+                    ("pv", Some((_, value))) if value == "pv3" => 800.0,
+                    ("active", None) => 1900.0,
+                    _ => f64::NAN,
+                },
+            )
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), 7.0);
+        assert_eq!(evaluate("(1 + 2) * 3"), 9.0);
+        assert_eq!(evaluate("10 - 4 - 3"), 3.0);
+        assert_eq!(evaluate("-2 * -3"), 6.0);
+        assert_eq!(evaluate("1 + 1 > 1.5"), 1.0);
+    }
+
+    #[test]
+    fn references() {
+        assert_eq!(evaluate(r#"pv{mppt="pv1"} + pv{mppt = "pv3"}"#), 2000.0);
+        assert_eq!(
+            evaluate(r#"active / (pv{mppt="pv1"} + pv{mppt="pv3"})"#),
+            0.95
+        );
+        assert!(evaluate("unknown + 1").is_nan());
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(evaluate("min(3, 1, 2)"), 1.0);
+        assert_eq!(evaluate("max(3, 1, 2)"), 3.0);
+        assert_eq!(evaluate("abs(-4)"), 4.0);
+        assert_eq!(evaluate("clamp(120, 0, 100)"), 100.0);
+        assert_eq!(evaluate("clamp(-5, 0, 100)"), 0.0);
+        assert_eq!(evaluate("if(active > 1000, 1, 2)"), 1.0);
+        assert_eq!(evaluate("if(active < 1000, 1, 2)"), 2.0);
+        assert!(evaluate("max(1, unknown)").is_nan());
+        assert!(evaluate("if(unknown > 0, 1, 2)").is_nan());
+        // The other branch doesn't matter
+        assert_eq!(evaluate("if(1, 5, unknown)"), 5.0);
+    }
+
+    #[test]
+    fn lists_references() {
+        let expression = Expression::parse(r#"max(pv{mppt="pv1"}, 0) / active"#).unwrap();
+        let references: Vec<String> = expression
+            .references()
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
+        assert_eq!(references, vec![r#"pv{mppt="pv1"}"#, "active"]);
+    }
+
+    #[test]
+    fn reports_errors_with_position() {
+        let error = |input| Expression::parse(input).unwrap_err();
+
+        assert_eq!(error("1 +").position, 3);
+        assert_eq!(error("1 + * 2").message, "unexpected character");
+        assert_eq!(error("(1 + 2").message, "expected ')'");
+        assert_eq!(error("sqrt(4)").message, "unknown function 'sqrt'");
+        assert_eq!(
+            error("clamp(1, 2)").message,
+            "wrong number of arguments for 'clamp'"
+        );
+        assert_eq!(error(r#"pv{mppt=pv1}"#).message, "expected '\"'");
+        assert_eq!(error("1.2.3").message, "invalid number");
+        assert_eq!(error("1 2").message, "unexpected input");
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |open: &str, close: &str, depth: usize| {
+            format!("{}1{}", open.repeat(depth), close.repeat(depth))
+        };
+        let too_deep = "expression is nested too deeply";
+
+        assert!(Expression::parse(&nested("(", ")", MAX_DEPTH)).is_ok());
+        assert_eq!(
+            Expression::parse(&nested("(", ")", 100_000))
+                .unwrap_err()
+                .message,
+            too_deep
+        );
+        assert_eq!(
+            Expression::parse(&nested("-", "", 100_000))
+                .unwrap_err()
+                .message,
+            too_deep
+        );
+        assert_eq!(
+            Expression::parse(&nested("abs(", ")", 100_000))
+                .unwrap_err()
+                .message,
+            too_deep
+        );
+        assert_eq!(
+            Expression::parse(&vec!["1"; 100_000].join(" + "))
+                .unwrap_err()
+                .message,
+            too_deep
+        );
+        // Depth is given back after each operand
+        assert_eq!(evaluate(&vec!["(-(1))"; MAX_DEPTH / 2].join(" * ")), 1.0);
+    }
+}
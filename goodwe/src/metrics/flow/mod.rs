@@ -0,0 +1,281 @@
+//! Where the energy flows, derived from the PV, battery and meter power.
+//!
+//! Sign conventions of the registers this is based on:
+//! - `power_battery_watts` (35182) is positive while the battery discharges
+//!   and negative while it charges.
+//! - `meter_active_power_watts{phase="all"}` (36025) is positive while power
+//!   is exported to the grid and negative while it is imported.
+//! - `power_pv_watts` (35105 and following) is never negative.
+//!
+//! Losses in the inverter are ignored, so the house consumption is what is
+//! left of PV, battery and grid power. PV power is assumed to cover the
+//! house first, then charge the battery, and only the rest is exported.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    definitions::{BaseMetric, MetricSet, MetricType, KV},
+    snapshot::{MeterData, RunningData},
+};
+
+const METRIC_PV_POWER: &str = "goodwe_power_pv_watts";
+const METRIC_BATTERY_POWER: &str = "goodwe_power_battery_watts";
+const METRIC_METER_POWER: &str = "goodwe_meter_active_power_watts";
+
+const FLOW: &str = "goodwe_power_flow_watts";
+const HOUSE: &str = "goodwe_house_consumption_watts";
+const SELF_CONSUMPTION: &str = "goodwe_self_consumption_ratio";
+const AUTARKY: &str = "goodwe_autarky_ratio";
+
+/// Names of the metrics the energy flow is exported as
+pub const METRIC_NAMES: [&str; 4] = [AUTARKY, HOUSE, FLOW, SELF_CONSUMPTION];
+
+const MPPTS: [&str; 4] = ["pv1", "pv2", "pv3", "pv4"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EnergyFlow {
+    /// All powers in W and never negative
+    pub house_consumption: f64,
+    pub pv_to_load: f64,
+    pub pv_to_battery: f64,
+    pub pv_to_grid: f64,
+    pub grid_to_battery: f64,
+    /// Share of the PV power used on site, NaN without PV power
+    pub self_consumption_ratio: f64,
+    /// Share of the house consumption not covered by the grid, NaN without
+    /// consumption
+    pub autarky_ratio: f64,
+}
+
+impl EnergyFlow {
+    /// Split up the given powers, using the sign conventions described above
+    pub fn new(pv_power: f64, battery_power: f64, meter_power: f64) -> Self {
+        let pv_power = pv_power.max(0.0);
+        let charge = (-battery_power).max(0.0);
+        let import = (-meter_power).max(0.0);
+
+        let house_consumption = (pv_power + battery_power - meter_power).max(0.0);
+        let pv_to_load = pv_power.min(house_consumption);
+        let pv_to_battery = (pv_power - pv_to_load).min(charge);
+        let pv_to_grid = pv_power - pv_to_load - pv_to_battery;
+        let grid_to_battery = (charge - pv_to_battery).min(import);
+        let grid_to_load = import - grid_to_battery;
+
+        Self {
+            house_consumption,
+            pv_to_load,
+            pv_to_battery,
+            pv_to_grid,
+            grid_to_battery,
+            self_consumption_ratio: ratio(pv_power - pv_to_grid, pv_power),
+            autarky_ratio: ratio(house_consumption - grid_to_load, house_consumption),
+        }
+    }
+
+    /// Derive the flow from the metric sets of a scrape, as long as the PV,
+    /// battery and meter power are among them
+    pub fn from_metric_sets(metric_sets: &[MetricSet]) -> Option<Self> {
+        let value = |name, key, label| metric_sets.iter().find_map(|ms| ms.value(name, key, label));
+
+        let mut pv_power = 0.0;
+        for mppt in MPPTS {
+            pv_power += value(METRIC_PV_POWER, "mppt", mppt)?;
+        }
+        let battery_power = value(METRIC_BATTERY_POWER, "none", "none")?;
+        let meter_power = value(METRIC_METER_POWER, "phase", "all")?;
+
+        Some(Self::new(pv_power, battery_power, meter_power))
+    }
+
+    pub fn from_snapshots(running: &RunningData, meter: &MeterData) -> Self {
+        let pv_power = running.pv.iter().map(|mppt| mppt.power as f64).sum();
+
+        Self::new(
+            pv_power,
+            running.battery.power as f64,
+            meter.total.active_power as f64,
+        )
+    }
+}
+
+fn ratio(part: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        part / total
+    } else {
+        f64::NAN
+    }
+}
+
+fn gauge(name: &str, labels: &[(&str, &str)]) -> BaseMetric {
+    let labels = labels
+        .iter()
+        .map(|(key, value)| KV::new(key.to_string(), value.to_string()))
+        .collect();
+    BaseMetric::new(MetricType::Gauge, name.to_owned(), labels, 0)
+}
+
+/// Prometheus text format, every family with its TYPE line directly before
+/// its samples, sorted by name like `Exposition`
+impl Display for EnergyFlow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# TYPE {AUTARKY} gauge")?;
+        writeln!(f, "{} {}", gauge(AUTARKY, &[]), self.autarky_ratio)?;
+
+        writeln!(f, "# TYPE {HOUSE} gauge")?;
+        writeln!(f, "{} {}", gauge(HOUSE, &[]), self.house_consumption)?;
+
+        writeln!(f, "# TYPE {FLOW} gauge")?;
+        for (from, to, value) in [
+            ("pv", "load", self.pv_to_load),
+            ("pv", "battery", self.pv_to_battery),
+            ("pv", "grid", self.pv_to_grid),
+            ("grid", "battery", self.grid_to_battery),
+        ] {
+            writeln!(
+                f,
+                "{} {}",
+                gauge(FLOW, &[("from", from), ("to", to)]),
+                value
+            )?;
+        }
+
+        writeln!(f, "# TYPE {SELF_CONSUMPTION} gauge")?;
+        writeln!(
+            f,
+            "{} {}",
+            gauge(SELF_CONSUMPTION, &[]),
+            self.self_consumption_ratio
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{
+        et,
+        planner::ReadBlock,
+        snapshot::{METER_DATA_COUNT, METER_DATA_START, RUNNING_DATA_COUNT, RUNNING_DATA_START},
+    };
+
+    /// Register image with the PV, battery and meter power set
+    fn images(pv: [i32; 4], battery: i32, meter: i32) -> (Vec<u8>, Vec<u8>) {
+        let mut running = vec![0_u8; RUNNING_DATA_COUNT as usize * 2];
+        let put = |data: &mut Vec<u8>, start: u16, register: u16, value: i32| {
+            let offset = (register - start) as usize * 2;
+            data[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+        };
+        for (index, power) in pv.into_iter().enumerate() {
+            put(
+                &mut running,
+                RUNNING_DATA_START,
+                35105 + 4 * index as u16,
+                power,
+            );
+        }
+        put(&mut running, RUNNING_DATA_START, 35182, battery);
+
+        let mut meter_data = vec![0_u8; METER_DATA_COUNT as usize * 2];
+        put(&mut meter_data, METER_DATA_START, 36025, meter);
+
+        (running, meter_data)
+    }
+
+    fn flow_from_metrics(running: &[u8], meter: &[u8]) -> EnergyFlow {
+        let mut base = et::base_metrics();
+        base.read_block(
+            &ReadBlock {
+                start: RUNNING_DATA_START,
+                count: RUNNING_DATA_COUNT,
+            },
+            running,
+        )
+        .unwrap();
+        let mut meter_metrics = et::meter_metrics();
+        meter_metrics
+            .read_block(
+                &ReadBlock {
+                    start: METER_DATA_START,
+                    count: METER_DATA_COUNT,
+                },
+                meter,
+            )
+            .unwrap();
+
+        EnergyFlow::from_metric_sets(&[base, meter_metrics]).unwrap()
+    }
+
+    fn flow(pv: [i32; 4], battery: i32, meter: i32) -> EnergyFlow {
+        let (running, meter_data) = images(pv, battery, meter);
+        let flow = flow_from_metrics(&running, &meter_data);
+
+        let snapshots = EnergyFlow::from_snapshots(
+            &RunningData::decode(&running).unwrap(),
+            &MeterData::decode(&meter_data).unwrap(),
+        );
+        assert_eq!(format!("{flow:?}"), format!("{snapshots:?}"));
+
+        flow
+    }
+
+    #[test]
+    fn sunny_day_charges_and_exports() {
+        // 6 kW PV, battery charging with 2 kW, 1.5 kW exported
+        let flow = flow([4000, 2000, 0, 0], -2000, 1500);
+
+        assert_eq!(flow.house_consumption, 2500.0);
+        assert_eq!(flow.pv_to_load, 2500.0);
+        assert_eq!(flow.pv_to_battery, 2000.0);
+        assert_eq!(flow.pv_to_grid, 1500.0);
+        assert_eq!(flow.grid_to_battery, 0.0);
+        assert_eq!(flow.self_consumption_ratio, 0.75);
+        assert_eq!(flow.autarky_ratio, 1.0);
+    }
+
+    #[test]
+    fn night_discharges_and_imports() {
+        // No PV, battery discharging with 800 W, 400 W imported
+        let flow = flow([0; 4], 800, -400);
+
+        assert_eq!(flow.house_consumption, 1200.0);
+        assert_eq!(flow.pv_to_load, 0.0);
+        assert_eq!(flow.pv_to_grid, 0.0);
+        assert!(flow.self_consumption_ratio.is_nan());
+        assert_eq!(flow.autarky_ratio, 800.0 / 1200.0);
+    }
+
+    #[test]
+    fn grid_charges_battery() {
+        // 1 kW PV, battery charging with 3 kW, 2.5 kW imported
+        let flow = flow([1000, 0, 0, 0], -3000, -2500);
+
+        assert_eq!(flow.house_consumption, 500.0);
+        assert_eq!(flow.pv_to_load, 500.0);
+        assert_eq!(flow.pv_to_battery, 500.0);
+        assert_eq!(flow.grid_to_battery, 2500.0);
+        assert_eq!(flow.self_consumption_ratio, 1.0);
+        assert_eq!(flow.autarky_ratio, 1.0);
+    }
+
+    #[test]
+    fn missing_metrics_give_no_flow() {
+        assert!(EnergyFlow::from_metric_sets(&[et::base_metrics()]).is_none());
+    }
+
+    #[test]
+    fn prometheus_output() {
+        let output = EnergyFlow::new(3000.0, 0.0, 1000.0).to_string();
+
+        assert!(output.contains("goodwe_house_consumption_watts {} 2000\n"));
+        assert!(output.contains("goodwe_power_flow_watts {from=\"pv\", to=\"grid\", } 1000\n"));
+        assert!(output.contains("goodwe_self_consumption_ratio {} 0.6666666666666666\n"));
+        // Every family directly follows its TYPE line
+        assert!(output.contains(
+            "# TYPE goodwe_house_consumption_watts gauge\n\
+             goodwe_house_consumption_watts {} 2000\n\
+             # TYPE goodwe_power_flow_watts gauge\n"
+        ));
+    }
+}
@@ -0,0 +1,143 @@
+use std::{error::Error, fmt::Display};
+
+use self::{
+    connection::Connection,
+    modbus::{ExceptionCode, ModbusError},
+    planner::{PlanOptions, ReadBlock},
+};
+use crate::transport::TransportOptions;
+
+pub mod connection;
+mod definitions;
+pub mod derived;
+pub mod expression;
+pub mod flow;
+pub mod modbus;
+mod planner;
+pub mod snapshot;
+
+pub mod et;
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum MetricsError {
+    MetricReadError(MetricReadError),
+    ModbusError(ModbusError),
+    NetworkError(std::io::Error),
+    InvalidWrite(&'static str),
+    VerificationFailed {
+        register: u16,
+        expected: u16,
+        actual: u16,
+    },
+    /// The register is not on the allow-list or the value is out of its range
+    WriteNotAllowed {
+        register: u16,
+        value: u16,
+    },
+    WriteCancelled,
+    AuditLogError(std::io::Error),
+}
+
+impl Display for MetricsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            MetricsError::MetricReadError(e) => write!(f, "Metric Read Error: {}", e),
+            MetricsError::NetworkError(e) => write!(f, "Network Error: {}", e),
+            MetricsError::ModbusError(e) => write!(f, "Modbus Error: {}", e),
+            MetricsError::InvalidWrite(reason) => write!(f, "Invalid Write: {}", reason),
+            MetricsError::VerificationFailed {
+                register,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Verification Error: register {} reads {} after writing {}",
+                register, actual, expected
+            ),
+            MetricsError::WriteNotAllowed { register, value } => write!(
+                f,
+                "Write Refused: writing {} to register {} is not allowed",
+                value, register
+            ),
+            MetricsError::WriteCancelled => write!(f, "Write cancelled"),
+            MetricsError::AuditLogError(e) => write!(f, "Audit Log Error: {}", e),
+        }
+    }
+}
+
+impl Error for MetricsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MetricsError::MetricReadError(e) => Some(e),
+            MetricsError::ModbusError(e) => Some(e),
+            MetricsError::NetworkError(e) | MetricsError::AuditLogError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+pub use definitions::{Exposition, MetricReadError, MetricSet};
+
+/// Exported along with the metrics, 1 if the inverter answered the scrape
+pub const METRIC_UP: &str = "goodwe_up";
+
+pub fn get_metrics(
+    target: &str,
+    transport: &TransportOptions,
+    ms: &mut MetricSet,
+) -> Result<(), MetricsError> {
+    read_metrics(&Connection::open_with(target, transport)?, ms)
+}
+
+/// Fill `ms` with the current values read over an already open connection
+pub fn read_metrics(conn: &Connection, ms: &mut MetricSet) -> Result<(), MetricsError> {
+    for block in ms.plan_reads(&PlanOptions::default()) {
+        match conn.read_registers(block.start, block.count) {
+            Ok(data) => ms
+                .read_block(&block, &data)
+                .map_err(MetricsError::MetricReadError)?,
+            Err(MetricsError::ModbusError(ModbusError::Exception(
+                ExceptionCode::IllegalDataAddress,
+            ))) => read_individually(conn, ms, &block)?,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+/// The inverter refused to read a block because at least one register in it
+/// is not supported by this model. Read the metrics one by one to find out
+/// which ones, and skip them in the future.
+fn read_individually(
+    conn: &Connection,
+    ms: &mut MetricSet,
+    block: &ReadBlock,
+) -> Result<(), MetricsError> {
+    for (register, width) in ms.ranges_in(block) {
+        let single = ReadBlock {
+            start: register,
+            count: width,
+        };
+        match conn.read_registers(single.start, single.count) {
+            Ok(data) => ms
+                .read_block(&single, &data)
+                .map_err(MetricsError::MetricReadError)?,
+            Err(MetricsError::ModbusError(ModbusError::Exception(
+                ExceptionCode::IllegalDataAddress,
+            ))) => ms.mark_unsupported(register),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+fn map_network_error(e: std::io::Error) -> MetricsError {
+    MetricsError::NetworkError(e)
+}
+
+fn map_modbus_error(e: ModbusError) -> MetricsError {
+    MetricsError::ModbusError(e)
+}
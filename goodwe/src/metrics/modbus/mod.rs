@@ -0,0 +1,410 @@
+use std::{error::Error, fmt::Display};
+
+use crc16::{State, MODBUS};
+
+pub const DEFAULT_ADDR: u8 = 0xf7;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    ReadMulti = 0x03,
+    WriteSingle = 0x06,
+    WriteMulti = 0x10,
+}
+
+/// Most registers a single `ReadMulti` command may return
+pub const MAX_READ_REGISTERS: u16 = 125;
+
+/// Most registers a single `WriteMulti` command may carry
+pub const MAX_WRITE_REGISTERS: usize = 123;
+
+/// Exception codes the inverter reports when it refuses a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionCode {
+    IllegalFunction,
+    IllegalDataAddress,
+    IllegalDataValue,
+    SlaveDeviceFailure,
+    Acknowledge,
+    SlaveDeviceBusy,
+    MemoryParityError,
+    GatewayPathUnavailable,
+    GatewayTargetFailedToRespond,
+    Unknown(u8),
+}
+
+impl From<u8> for ExceptionCode {
+    fn from(code: u8) -> Self {
+        match code {
+            0x01 => ExceptionCode::IllegalFunction,
+            0x02 => ExceptionCode::IllegalDataAddress,
+            0x03 => ExceptionCode::IllegalDataValue,
+            0x04 => ExceptionCode::SlaveDeviceFailure,
+            0x05 => ExceptionCode::Acknowledge,
+            0x06 => ExceptionCode::SlaveDeviceBusy,
+            0x08 => ExceptionCode::MemoryParityError,
+            0x0a => ExceptionCode::GatewayPathUnavailable,
+            0x0b => ExceptionCode::GatewayTargetFailedToRespond,
+            other => ExceptionCode::Unknown(other),
+        }
+    }
+}
+
+impl From<ExceptionCode> for u8 {
+    fn from(code: ExceptionCode) -> Self {
+        match code {
+            ExceptionCode::IllegalFunction => 0x01,
+            ExceptionCode::IllegalDataAddress => 0x02,
+            ExceptionCode::IllegalDataValue => 0x03,
+            ExceptionCode::SlaveDeviceFailure => 0x04,
+            ExceptionCode::Acknowledge => 0x05,
+            ExceptionCode::SlaveDeviceBusy => 0x06,
+            ExceptionCode::MemoryParityError => 0x08,
+            ExceptionCode::GatewayPathUnavailable => 0x0a,
+            ExceptionCode::GatewayTargetFailedToRespond => 0x0b,
+            ExceptionCode::Unknown(code) => code,
+        }
+    }
+}
+
+impl Display for ExceptionCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExceptionCode::IllegalFunction => write!(f, "Illegal function"),
+            ExceptionCode::IllegalDataAddress => write!(f, "Illegal data address"),
+            ExceptionCode::IllegalDataValue => write!(f, "Illegal data value"),
+            ExceptionCode::SlaveDeviceFailure => write!(f, "Slave device failure"),
+            ExceptionCode::Acknowledge => write!(f, "Acknowledge"),
+            ExceptionCode::SlaveDeviceBusy => write!(f, "Slave device busy"),
+            ExceptionCode::MemoryParityError => write!(f, "Memory parity error"),
+            ExceptionCode::GatewayPathUnavailable => write!(f, "Gateway path unavailable"),
+            ExceptionCode::GatewayTargetFailedToRespond => {
+                write!(f, "Gateway target device failed to respond")
+            }
+            ExceptionCode::Unknown(code) => write!(f, "Unknown exception code {code:#04x}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ModbusError {
+    InvalidHeader,
+    WrongChecksum,
+    Exception(ExceptionCode),
+    PayloadLength,
+    /// The frame is valid, but does not answer the request that was sent
+    ResponseMismatch {
+        field: &'static str,
+        expected: u16,
+        actual: u16,
+    },
+}
+
+impl Display for ModbusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            ModbusError::InvalidHeader => write!(f, "Invalid Header"),
+            ModbusError::WrongChecksum => write!(f, "CRC-16 checksum wrong, data was corrupted"),
+            ModbusError::Exception(code) => write!(f, "Command failed: {code}"),
+            ModbusError::PayloadLength => {
+                write!(f, "Indicated payload length does not match actual length")
+            }
+            ModbusError::ResponseMismatch {
+                field,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Response does not match request, expected {field} {expected:#x} but got {actual:#x}"
+            ),
+        }
+    }
+}
+
+impl Error for ModbusError {}
+
+/// CRC-16 of `data` in the byte order it is sent in
+pub fn crc(data: &[u8]) -> Vec<u8> {
+    let checksum = State::<MODBUS>::calculate(data);
+
+    vec![(checksum & 0xff) as u8, ((checksum >> 8) & 0xff) as u8]
+}
+
+pub fn create_command(cmd: Command, addr: u8, reg: u16, param: u16) -> Vec<u8> {
+    let mut data: Vec<u8> = vec![
+        addr,
+        cmd as u8,
+        ((reg >> 8) & 0xff) as u8,
+        (reg & 0xff) as u8,
+        ((param >> 8) & 0xff) as u8,
+        (param & 0xff) as u8,
+    ];
+
+    data.append(&mut crc(&data));
+    data
+}
+
+/// Command writing `values` to consecutive registers starting at `reg`.
+/// Panics with more than `MAX_WRITE_REGISTERS` values, their byte count
+/// wouldn't fit the frame.
+pub fn create_write_multi_command(addr: u8, reg: u16, values: &[u16]) -> Vec<u8> {
+    assert!(
+        values.len() <= MAX_WRITE_REGISTERS,
+        "can write at most {MAX_WRITE_REGISTERS} registers at once, got {}",
+        values.len()
+    );
+    let count = values.len() as u16;
+    let mut data: Vec<u8> = vec![
+        addr,
+        Command::WriteMulti as u8,
+        ((reg >> 8) & 0xff) as u8,
+        (reg & 0xff) as u8,
+        ((count >> 8) & 0xff) as u8,
+        (count & 0xff) as u8,
+        (values.len() * 2) as u8,
+    ];
+    for value in values {
+        data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    data.append(&mut crc(&data));
+    data
+}
+
+/// Split register data as returned by `get_payload` into 16 bit words
+pub fn to_registers(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]))
+        .collect()
+}
+
+/// Smallest valid frame: AA55 header, address, command, length or exception
+/// code and the CRC
+const MIN_FRAME_LENGTH: usize = 7;
+
+/// Length of the answer to a write command: AA55 header, address, command,
+/// register, value or count and the CRC
+const WRITE_RESPONSE_LENGTH: usize = 10;
+
+/// Validate the parts all answers have in common: the header, checksum,
+/// address and function. Exception responses are turned into errors.
+fn check_frame(data: &[u8], addr: u8, cmd: Command) -> Result<(), ModbusError> {
+    // We expect the AA55 header
+    if !data.starts_with(&[0xaa, 0x55]) {
+        return Err(ModbusError::InvalidHeader);
+    }
+
+    if data.len() < MIN_FRAME_LENGTH {
+        return Err(ModbusError::PayloadLength);
+    }
+
+    // CRC, The AA55 header is not part of the CRC
+    if State::<MODBUS>::calculate(&data[2..]) != 0 {
+        return Err(ModbusError::WrongChecksum);
+    }
+
+    // Make sure we are looking at the answer to our request and not at a
+    // late answer to an earlier one
+    check_field("address", addr as u16, data[2] as u16)?;
+    check_field("function", cmd as u16, (data[3] & 0x7f) as u16)?;
+
+    // Modbus Command. If the highest bit is set to 1, the command failed
+    // and the next byte holds the exception code.
+    if (data[3] & 0x80) > 0 {
+        return Err(ModbusError::Exception(ExceptionCode::from(data[4])));
+    }
+
+    Ok(())
+}
+
+fn check_field(field: &'static str, expected: u16, actual: u16) -> Result<(), ModbusError> {
+    if expected != actual {
+        return Err(ModbusError::ResponseMismatch {
+            field,
+            expected,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Extract the register data from the answer to a `ReadMulti` command sent
+/// to `addr` for `count` registers.
+pub fn get_payload(data: &[u8], addr: u8, count: u16) -> Result<Vec<u8>, ModbusError> {
+    // We do not get real Modbus packets back, but they look like AA55 protocol packets
+    // Let's validate them anyway.
+    check_frame(data, addr, Command::ReadMulti)?;
+
+    // Does the actual remaining length match the advertised payload length
+    let payload_length = data[4] as usize;
+    if data.len() != MIN_FRAME_LENGTH + payload_length {
+        return Err(ModbusError::PayloadLength);
+    }
+    check_field("byte count", count.saturating_mul(2), payload_length as u16)?;
+
+    Ok(data[5..(5 + payload_length)].to_vec())
+}
+
+/// Validate the answer to a write command. `WriteSingle` echoes register and
+/// value, `WriteMulti` echoes the start register and the number of registers
+/// written, which have to be passed as `param`.
+pub fn check_write_response(
+    data: &[u8],
+    addr: u8,
+    cmd: Command,
+    reg: u16,
+    param: u16,
+) -> Result<(), ModbusError> {
+    check_frame(data, addr, cmd)?;
+
+    if data.len() != WRITE_RESPONSE_LENGTH {
+        return Err(ModbusError::PayloadLength);
+    }
+
+    check_field("register", reg, u16::from_be_bytes([data[4], data[5]]))?;
+    let param_name = match cmd {
+        Command::WriteMulti => "register count",
+        _ => "value",
+    };
+    check_field(param_name, param, u16::from_be_bytes([data[6], data[7]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn frame(body: &[u8]) -> Vec<u8> {
+        let mut data = vec![0xaa, 0x55];
+        data.extend_from_slice(body);
+        data.append(&mut crc(body));
+        data
+    }
+
+    proptest! {
+        #[test]
+        fn get_payload_accepts_any_input(
+            data in proptest::collection::vec(any::<u8>(), 0..300),
+            count: u16,
+        ) {
+            let _ = get_payload(&data, DEFAULT_ADDR, count);
+        }
+
+        #[test]
+        fn get_payload_accepts_any_framed_input(
+            body in proptest::collection::vec(any::<u8>(), 0..300),
+            count: u16,
+        ) {
+            let _ = get_payload(&frame(&body), DEFAULT_ADDR, count);
+        }
+
+        #[test]
+        fn get_payload_returns_payload(count in 0_u16..=125, seed: u8) {
+            let payload: Vec<u8> = (0..count * 2).map(|i| seed.wrapping_add(i as u8)).collect();
+            let mut body = vec![DEFAULT_ADDR, Command::ReadMulti as u8, payload.len() as u8];
+            body.extend_from_slice(&payload);
+            prop_assert_eq!(get_payload(&frame(&body), DEFAULT_ADDR, count).unwrap(), payload);
+        }
+
+        #[test]
+        fn get_payload_decodes_exceptions(code: u8) {
+            let cmd = Command::ReadMulti as u8 | 0x80;
+            match get_payload(&frame(&[DEFAULT_ADDR, cmd, code]), DEFAULT_ADDR, 1) {
+                Err(ModbusError::Exception(exception)) => {
+                    prop_assert_eq!(exception, ExceptionCode::from(code))
+                }
+                _ => prop_assert!(false, "exception not decoded"),
+            }
+        }
+    }
+
+    #[test]
+    fn get_payload_rejects_truncated_frame() {
+        let data = frame(&[DEFAULT_ADDR, Command::ReadMulti as u8, 4, 0x01, 0x02]);
+        assert!(matches!(
+            get_payload(&data, DEFAULT_ADDR, 2),
+            Err(ModbusError::PayloadLength)
+        ));
+    }
+
+    #[test]
+    fn get_payload_rejects_corrupted_frame() {
+        let mut data = frame(&[DEFAULT_ADDR, Command::ReadMulti as u8, 2, 0x01, 0x02]);
+        data[5] ^= 0xff;
+        assert!(matches!(
+            get_payload(&data, DEFAULT_ADDR, 1),
+            Err(ModbusError::WrongChecksum)
+        ));
+    }
+
+    #[test]
+    fn get_payload_rejects_answers_to_other_requests() {
+        let data = frame(&[DEFAULT_ADDR, Command::ReadMulti as u8, 2, 0x01, 0x02]);
+        for (addr, count, mismatch) in [(0x01, 1, "address"), (DEFAULT_ADDR, 2, "byte count")] {
+            match get_payload(&data, addr, count) {
+                Err(ModbusError::ResponseMismatch { field, .. }) => assert_eq!(field, mismatch),
+                _ => panic!("{mismatch} mismatch not detected"),
+            }
+        }
+
+        let data = frame(&[DEFAULT_ADDR, Command::WriteSingle as u8, 2, 0x01, 0x02]);
+        assert!(matches!(
+            get_payload(&data, DEFAULT_ADDR, 1),
+            Err(ModbusError::ResponseMismatch {
+                field: "function",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn create_write_multi_command_encodes_values() {
+        let cmd = create_write_multi_command(DEFAULT_ADDR, 47547, &[0x0102, 0x0304]);
+        assert_eq!(
+            cmd[..11],
+            [
+                DEFAULT_ADDR,
+                0x10,
+                0xb9,
+                0xbb,
+                0x00,
+                0x02,
+                0x04,
+                0x01,
+                0x02,
+                0x03,
+                0x04
+            ]
+        );
+        assert_eq!(State::<MODBUS>::calculate(&cmd), 0);
+    }
+
+    #[test]
+    fn create_write_multi_command_takes_up_to_max_registers() {
+        let values = [0_u16; MAX_WRITE_REGISTERS];
+        let cmd = create_write_multi_command(DEFAULT_ADDR, 0, &values);
+        assert_eq!(cmd[4..7], [0x00, 123, 246]);
+        assert_eq!(cmd.len(), 7 + 2 * MAX_WRITE_REGISTERS + 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "can write at most 123 registers")]
+    fn create_write_multi_command_rejects_too_many_registers() {
+        create_write_multi_command(DEFAULT_ADDR, 0, &[0; MAX_WRITE_REGISTERS + 1]);
+    }
+
+    #[test]
+    fn check_write_response_validates_echo() {
+        let data = frame(&[DEFAULT_ADDR, 0x06, 0xb7, 0x98, 0x00, 0x03]);
+        assert!(check_write_response(&data, DEFAULT_ADDR, Command::WriteSingle, 47000, 3).is_ok());
+        assert!(matches!(
+            check_write_response(&data, DEFAULT_ADDR, Command::WriteSingle, 47000, 1),
+            Err(ModbusError::ResponseMismatch { field: "value", .. })
+        ));
+
+        let data = frame(&[DEFAULT_ADDR, 0x90, 0x02]);
+        assert!(matches!(
+            check_write_response(&data, DEFAULT_ADDR, Command::WriteMulti, 47547, 6),
+            Err(ModbusError::Exception(ExceptionCode::IllegalDataAddress))
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+/// Maximum number of registers a single Modbus read may return
+pub const MAX_BLOCK_SIZE: u16 = super::modbus::MAX_READ_REGISTERS;
+
+/// Number of unused registers that are read rather than starting a new
+/// request. Each request costs a full round trip to the inverter, so
+/// reading a few registers too many is cheaper.
+pub const DEFAULT_MAX_GAP: u16 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadBlock {
+    pub start: u16,
+    pub count: u16,
+}
+
+impl ReadBlock {
+    fn end(&self) -> u32 {
+        self.start as u32 + self.count as u32
+    }
+
+    /// Whether the registers `register..register + width` are part of the block
+    pub fn contains(&self, register: u16, width: u16) -> bool {
+        register >= self.start && (register as u32 + width as u32) <= self.end()
+    }
+}
+
+pub struct PlanOptions {
+    pub max_gap: u16,
+    pub max_block_size: u16,
+}
+
+impl Default for PlanOptions {
+    fn default() -> Self {
+        Self {
+            max_gap: DEFAULT_MAX_GAP,
+            max_block_size: MAX_BLOCK_SIZE,
+        }
+    }
+}
+
+/// Group register ranges, given as start register and width, into the
+/// fewest read requests that respect the gap and block size limits. Gaps
+/// containing one of the `excluded` registers are never bridged, so no
+/// block includes them.
+///
+/// Ranges are sorted and merged greedily from the lowest register onwards,
+/// which yields the minimal number of blocks for interval sets like these.
+pub fn plan_reads(
+    ranges: impl IntoIterator<Item = (u16, u16)>,
+    excluded: &HashSet<u16>,
+    options: &PlanOptions,
+) -> Vec<ReadBlock> {
+    let mut ranges: Vec<(u16, u16)> = ranges.into_iter().filter(|r| r.1 > 0).collect();
+    ranges.sort();
+
+    let mut blocks: Vec<ReadBlock> = Vec::new();
+    for (register, width) in ranges {
+        let range_end = register as u32 + width as u32;
+
+        if let Some(block) = blocks.last_mut() {
+            let merged_end = block.end().max(range_end);
+            let bridges_excluded =
+                (block.end()..register as u32).any(|r| excluded.contains(&(r as u16)));
+            if register as u32 <= block.end() + options.max_gap as u32
+                && merged_end - block.start as u32 <= options.max_block_size as u32
+                && !bridges_excluded
+            {
+                block.count = (merged_end - block.start as u32) as u16;
+                continue;
+            }
+        }
+
+        blocks.push(ReadBlock {
+            start: register,
+            count: width,
+        });
+    }
+
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(start: u16, count: u16) -> ReadBlock {
+        ReadBlock { start, count }
+    }
+
+    #[test]
+    fn merges_small_gaps_and_splits_on_large_ones() {
+        let blocks = plan_reads(
+            [(100, 1), (105, 2), (110, 1), (200, 1)],
+            &HashSet::new(),
+            &PlanOptions::default(),
+        );
+        assert_eq!(blocks, vec![block(100, 11), block(200, 1)]);
+    }
+
+    #[test]
+    fn respects_block_size_limit() {
+        let ranges = (0..300).map(|register| (35000 + register, 1));
+        let blocks = plan_reads(ranges, &HashSet::new(), &PlanOptions::default());
+
+        assert_eq!(
+            blocks,
+            vec![block(35000, 125), block(35125, 125), block(35250, 50)]
+        );
+    }
+
+    #[test]
+    fn includes_full_width_of_last_metric() {
+        let blocks = plan_reads(
+            [(35103, 1), (35105, 2)],
+            &HashSet::new(),
+            &PlanOptions::default(),
+        );
+        assert_eq!(blocks, vec![block(35103, 4)]);
+
+        // A two-word metric that doesn't fit starts a new block as a whole
+        let options = PlanOptions {
+            max_gap: 0,
+            max_block_size: 3,
+        };
+        let blocks = plan_reads([(1, 2), (3, 2)], &HashSet::new(), &options);
+        assert_eq!(blocks, vec![block(1, 2), block(3, 2)]);
+    }
+
+    #[test]
+    fn ignores_order_duplicates_and_empty_ranges() {
+        let blocks = plan_reads(
+            [(10, 2), (5, 0), (8, 1), (10, 2)],
+            &HashSet::new(),
+            &PlanOptions::default(),
+        );
+        assert_eq!(blocks, vec![block(8, 4)]);
+    }
+
+    #[test]
+    fn never_bridges_excluded_registers() {
+        let excluded = HashSet::from([103]);
+        let blocks = plan_reads([(100, 2), (105, 1)], &excluded, &PlanOptions::default());
+        assert_eq!(blocks, vec![block(100, 2), block(105, 1)]);
+    }
+}
@@ -0,0 +1,635 @@
+use serde::{Deserialize, Serialize};
+
+use super::definitions::MetricReadError;
+
+/// Register block holding the running data, the same one `et::base_metrics`
+/// reads apart from the PV total power, which is too far off
+pub const RUNNING_DATA_START: u16 = 35100;
+pub const RUNNING_DATA_COUNT: u16 = 122;
+
+/// Register block holding the BMS data, the same one `et::battery_metrics` reads
+pub const BMS_DATA_START: u16 = 37000;
+pub const BMS_DATA_COUNT: u16 = 24;
+
+/// Register block holding the meter data, the same one `et::meter_metrics` reads
+pub const METER_DATA_START: u16 = 36000;
+pub const METER_DATA_COUNT: u16 = 58;
+
+/// Values of a single MPPT tracker
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MpptReading {
+    /// V
+    pub voltage: f32,
+    /// A
+    pub current: f32,
+    /// W
+    pub power: i32,
+}
+
+/// Values of a single phase of the grid or backup side
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhaseReading {
+    /// V
+    pub voltage: f32,
+    /// A
+    pub current: f32,
+    /// Hz
+    pub frequency: f32,
+    /// W
+    pub power: i32,
+}
+
+/// Consumption in W
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoadReading {
+    pub phases: [i32; 3],
+    pub backup: i32,
+    pub total: i32,
+}
+
+/// Temperatures in °C
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Temperatures {
+    pub air: f32,
+    pub module: f32,
+    pub radiator: f32,
+}
+
+/// Battery values as seen by the inverter
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BatteryReading {
+    /// V
+    pub voltage: f32,
+    /// A
+    pub current: f32,
+    /// W, positive while discharging
+    pub power: i32,
+}
+
+/// Energy counters in kWh
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EnergyTotals {
+    pub pv_generation_total: f32,
+    pub pv_generation_today: f32,
+    pub export_total: f32,
+    pub export_today: f32,
+    pub import_total: f32,
+    pub import_today: f32,
+    pub load_total: f32,
+    pub load_today: f32,
+    pub battery_charge_total: f32,
+    pub battery_charge_today: f32,
+    pub battery_discharge_total: f32,
+    pub battery_discharge_today: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RunningData {
+    pub pv: [MpptReading; 4],
+    pub grid: [PhaseReading; 3],
+    /// W
+    pub inverter_power: i32,
+    /// W, positive while exporting
+    pub active_power: i32,
+    /// var
+    pub reactive_power: i32,
+    /// VA
+    pub apparent_power: i32,
+    pub backup: [PhaseReading; 3],
+    /// Raw mode codes of the backup phases
+    pub backup_modes: [u16; 3],
+    pub load: LoadReading,
+    /// %
+    pub backup_utilization: u16,
+    pub temperatures: Temperatures,
+    /// V
+    pub bus_voltage: f32,
+    /// V
+    pub nbus_voltage: f32,
+    /// Raw function bits
+    pub function_bits: u16,
+    pub battery: BatteryReading,
+    pub warning_code: u16,
+    pub safety_country: u16,
+    /// Raw operation mode code
+    pub operation_mode: u16,
+    /// Error bits, the high word in the upper 16 bits
+    pub errors: u32,
+    /// h
+    pub operation_hours: u32,
+    pub energy: EnergyTotals,
+    /// Diagnostic status bits, the high word in the upper 16 bits
+    pub diagnostic_status: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BmsData {
+    pub bms: i16,
+    pub index: i16,
+    pub status: i16,
+    /// °C
+    pub temperature: f32,
+    /// A
+    pub charge_current_limit: i16,
+    /// A
+    pub discharge_current_limit: i16,
+    /// %
+    pub state_of_charge: u16,
+    /// %
+    pub state_of_health: u16,
+    pub modules: i16,
+    /// Error bits, the high word in the upper 16 bits
+    pub errors: u32,
+    /// Warning bits, the high word in the upper 16 bits
+    pub warnings: u32,
+    pub software_version: i16,
+    pub hardware_version: i16,
+    pub max_cell_temperature_id: i16,
+    pub min_cell_temperature_id: i16,
+    pub max_cell_voltage_id: i16,
+    pub min_cell_voltage_id: i16,
+    /// °C
+    pub max_cell_temperature: f32,
+    /// °C
+    pub min_cell_temperature: f32,
+    /// V
+    pub max_cell_voltage: f32,
+    /// V
+    pub min_cell_voltage: f32,
+}
+
+/// Values the meter measured on a single phase, or on all of them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MeterPhaseReading {
+    /// W, positive while exporting
+    pub active_power: i32,
+    /// var
+    pub reactive_power: i32,
+    /// VA
+    pub apparent_power: i32,
+    pub power_factor: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MeterData {
+    pub comm_mode: i16,
+    pub rssi: i16,
+    pub manufacture_code: i16,
+    /// 1: correct, 2: reverse, 3: incorrect, 0: not checked
+    pub test_status: i16,
+    /// 1: OK, 0: NOK
+    pub comm_status: i16,
+    pub phases: [MeterPhaseReading; 3],
+    pub total: MeterPhaseReading,
+    /// V per phase
+    pub voltages: [f32; 3],
+    /// A per phase
+    pub currents: [f32; 3],
+    /// Hz
+    pub frequency: f32,
+    /// kWh
+    pub export_energy: f32,
+    /// kWh
+    pub import_energy: f32,
+    /// 0: Single Phase, 1: 3P3W, 2: 3P4W, 3: HomeKit
+    pub meter_type: i16,
+    pub software_version: i16,
+}
+
+/// Registers of a block as returned by the inverter, with accessors using
+/// the absolute register numbers
+struct Block<'a> {
+    start: u16,
+    data: &'a [u8],
+    /// First register and width of every value decoded, to compare the
+    /// snapshots with the metric definitions
+    #[cfg(test)]
+    reads: std::cell::RefCell<Vec<(u16, u16)>>,
+}
+
+impl<'a> Block<'a> {
+    fn new(start: u16, data: &'a [u8]) -> Self {
+        Self {
+            start,
+            data,
+            #[cfg(test)]
+            reads: Default::default(),
+        }
+    }
+
+    #[cfg(test)]
+    fn record(&self, register: u16, width: u16) {
+        self.reads.borrow_mut().push((register, width));
+    }
+
+    #[cfg(not(test))]
+    fn record(&self, _register: u16, _width: u16) {}
+
+    fn bytes<const WIDTH: usize>(&self, register: u16) -> Result<[u8; WIDTH], MetricReadError> {
+        self.record(register, WIDTH as u16 / 2);
+        self.raw_bytes(register)
+    }
+
+    fn raw_bytes<const WIDTH: usize>(&self, register: u16) -> Result<[u8; WIDTH], MetricReadError> {
+        let offset = register
+            .checked_sub(self.start)
+            .ok_or(MetricReadError::OutOfBounds)? as usize
+            * 2;
+        self.data
+            .get(offset..offset + WIDTH)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(MetricReadError::OutOfBounds)
+    }
+
+    fn u16(&self, register: u16) -> Result<u16, MetricReadError> {
+        Ok(u16::from_be_bytes(self.bytes(register)?))
+    }
+
+    fn i16(&self, register: u16) -> Result<i16, MetricReadError> {
+        Ok(i16::from_be_bytes(self.bytes(register)?))
+    }
+
+    fn i32(&self, register: u16) -> Result<i32, MetricReadError> {
+        Ok(i32::from_be_bytes(self.bytes(register)?))
+    }
+
+    /// Two words that may be apart, recorded as the range they span
+    fn u32(&self, high: u16, low: u16) -> Result<u32, MetricReadError> {
+        self.record(high.min(low), high.abs_diff(low) + 1);
+        let word =
+            |register| Ok::<_, MetricReadError>(u16::from_be_bytes(self.raw_bytes(register)?));
+        Ok((word(high)? as u32) << 16 | word(low)? as u32)
+    }
+
+    fn f32(&self, register: u16) -> Result<f32, MetricReadError> {
+        Ok(f32::from_be_bytes(self.bytes(register)?))
+    }
+
+    /// Signed value with the given number of decimal places
+    fn scaled(&self, register: u16, divisor: f32) -> Result<f32, MetricReadError> {
+        Ok(self.i16(register)? as f32 / divisor)
+    }
+
+    fn large_scaled(&self, register: u16, divisor: f32) -> Result<f32, MetricReadError> {
+        Ok(self.i32(register)? as f32 / divisor)
+    }
+}
+
+impl RunningData {
+    /// Decode the registers read from `RUNNING_DATA_START`
+    pub fn decode(data: &[u8]) -> Result<Self, MetricReadError> {
+        Self::decode_block(&Block::new(RUNNING_DATA_START, data))
+    }
+
+    fn decode_block(block: &Block) -> Result<Self, MetricReadError> {
+        let mppt = |register| -> Result<MpptReading, MetricReadError> {
+            Ok(MpptReading {
+                voltage: block.scaled(register, 10.0)?,
+                current: block.scaled(register + 1, 10.0)?,
+                power: block.i32(register + 2)?,
+            })
+        };
+        // The backup side has a mode register before the power
+        let phase = |register, power_offset| -> Result<PhaseReading, MetricReadError> {
+            Ok(PhaseReading {
+                voltage: block.scaled(register, 10.0)?,
+                current: block.scaled(register + 1, 10.0)?,
+                frequency: block.scaled(register + 2, 100.0)?,
+                power: block.i16(register + power_offset)? as i32,
+            })
+        };
+
+        Ok(Self {
+            pv: [mppt(35103)?, mppt(35107)?, mppt(35111)?, mppt(35115)?],
+            grid: [phase(35121, 4)?, phase(35126, 4)?, phase(35131, 4)?],
+            inverter_power: block.i16(35138)? as i32,
+            active_power: block.i16(35140)? as i32,
+            reactive_power: block.i16(35142)? as i32,
+            apparent_power: block.i16(35144)? as i32,
+            backup: [phase(35145, 5)?, phase(35151, 5)?, phase(35157, 5)?],
+            backup_modes: [block.u16(35148)?, block.u16(35154)?, block.u16(35160)?],
+            load: LoadReading {
+                phases: [
+                    block.i16(35164)? as i32,
+                    block.i16(35166)? as i32,
+                    block.i16(35168)? as i32,
+                ],
+                backup: block.i16(35170)? as i32,
+                total: block.i16(35172)? as i32,
+            },
+            backup_utilization: block.u16(35173)?,
+            temperatures: Temperatures {
+                air: block.scaled(35174, 10.0)?,
+                module: block.scaled(35175, 10.0)?,
+                radiator: block.scaled(35176, 10.0)?,
+            },
+            function_bits: block.u16(35177)?,
+            bus_voltage: block.scaled(35178, 10.0)?,
+            nbus_voltage: block.scaled(35179, 10.0)?,
+            battery: BatteryReading {
+                voltage: block.scaled(35180, 10.0)?,
+                current: block.scaled(35181, 10.0)?,
+                power: block.i32(35182)?,
+            },
+            warning_code: block.u16(35185)?,
+            safety_country: block.u16(35186)?,
+            operation_mode: block.u16(35188)?,
+            errors: block.u32(35189, 35190)?,
+            operation_hours: block.u32(35197, 35198)?,
+            energy: EnergyTotals {
+                pv_generation_total: block.large_scaled(35191, 10.0)?,
+                pv_generation_today: block.large_scaled(35193, 10.0)?,
+                export_total: block.large_scaled(35195, 10.0)?,
+                export_today: block.scaled(35199, 10.0)?,
+                import_total: block.large_scaled(35200, 10.0)?,
+                import_today: block.scaled(35202, 10.0)?,
+                load_total: block.large_scaled(35203, 10.0)?,
+                load_today: block.scaled(35205, 10.0)?,
+                battery_charge_total: block.large_scaled(35206, 10.0)?,
+                battery_charge_today: block.scaled(35208, 10.0)?,
+                battery_discharge_total: block.large_scaled(35209, 10.0)?,
+                battery_discharge_today: block.scaled(35211, 10.0)?,
+            },
+            diagnostic_status: block.u32(35220, 35221)?,
+        })
+    }
+}
+
+impl BmsData {
+    /// Decode the registers read from `BMS_DATA_START`
+    pub fn decode(data: &[u8]) -> Result<Self, MetricReadError> {
+        Self::decode_block(&Block::new(BMS_DATA_START, data))
+    }
+
+    fn decode_block(block: &Block) -> Result<Self, MetricReadError> {
+        Ok(Self {
+            bms: block.i16(37000)?,
+            index: block.i16(37001)?,
+            status: block.i16(37002)?,
+            temperature: block.scaled(37003, 10.0)?,
+            charge_current_limit: block.i16(37004)?,
+            discharge_current_limit: block.i16(37005)?,
+            state_of_charge: block.u16(37007)?,
+            state_of_health: block.u16(37008)?,
+            modules: block.i16(37009)?,
+            errors: block.u32(37011, 37006)?,
+            warnings: block.u32(37013, 37010)?,
+            software_version: block.i16(37014)?,
+            hardware_version: block.i16(37015)?,
+            max_cell_temperature_id: block.i16(37016)?,
+            min_cell_temperature_id: block.i16(37017)?,
+            max_cell_voltage_id: block.i16(37018)?,
+            min_cell_voltage_id: block.i16(37019)?,
+            max_cell_temperature: block.scaled(37020, 10.0)?,
+            min_cell_temperature: block.scaled(37021, 10.0)?,
+            max_cell_voltage: block.scaled(37022, 10.0)?,
+            min_cell_voltage: block.scaled(37023, 10.0)?,
+        })
+    }
+}
+
+impl MeterData {
+    /// Decode the registers read from `METER_DATA_START`
+    pub fn decode(data: &[u8]) -> Result<Self, MetricReadError> {
+        Self::decode_block(&Block::new(METER_DATA_START, data))
+    }
+
+    fn decode_block(block: &Block) -> Result<Self, MetricReadError> {
+        // Active, reactive and apparent power are 8 registers apart
+        let phase = |index: u16| -> Result<MeterPhaseReading, MetricReadError> {
+            Ok(MeterPhaseReading {
+                active_power: block.i32(36019 + 2 * index)?,
+                reactive_power: block.i32(36027 + 2 * index)?,
+                apparent_power: block.i32(36035 + 2 * index)?,
+                power_factor: block.scaled(36010 + index, 1000.0)?,
+            })
+        };
+
+        Ok(Self {
+            comm_mode: block.i16(36000)?,
+            rssi: block.i16(36001)?,
+            manufacture_code: block.i16(36002)?,
+            test_status: block.i16(36003)?,
+            comm_status: block.i16(36004)?,
+            phases: [phase(0)?, phase(1)?, phase(2)?],
+            total: phase(3)?,
+            voltages: [
+                block.scaled(36052, 10.0)?,
+                block.scaled(36053, 10.0)?,
+                block.scaled(36054, 10.0)?,
+            ],
+            currents: [
+                block.scaled(36055, 10.0)?,
+                block.scaled(36056, 10.0)?,
+                block.scaled(36057, 10.0)?,
+            ],
+            frequency: block.scaled(36014, 100.0)?,
+            export_energy: block.f32(36015)?,
+            import_energy: block.f32(36017)?,
+            meter_type: block.i16(36043)?,
+            software_version: block.i16(36044)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{et, planner::ReadBlock, MetricSet};
+
+    fn block_data(start: u16, count: u16, values: &[(u16, &[u16])]) -> Vec<u8> {
+        let mut registers = vec![0_u16; count as usize];
+        for (register, words) in values {
+            let offset = (register - start) as usize;
+            registers[offset..offset + words.len()].copy_from_slice(words);
+        }
+        registers.iter().flat_map(|r| r.to_be_bytes()).collect()
+    }
+
+    /// Every metric of the set has to be inside the block the snapshot
+    /// reads, except for the given registers
+    fn assert_covers(ms: &MetricSet, start: u16, count: u16, except: &[u16]) {
+        let block = ReadBlock { start, count };
+        for metric in ms
+            .metrics
+            .iter()
+            .filter(|m| !except.contains(&m.get_register()))
+        {
+            assert!(
+                block.contains(metric.get_register(), metric.get_width()),
+                "register {} is outside the block",
+                metric.get_register()
+            );
+        }
+    }
+
+    #[test]
+    fn blocks_cover_metric_sets() {
+        // PV total power
+        assert_covers(
+            &et::base_metrics(),
+            RUNNING_DATA_START,
+            RUNNING_DATA_COUNT,
+            &[35301],
+        );
+        assert_covers(&et::battery_metrics(), BMS_DATA_START, BMS_DATA_COUNT, &[]);
+        assert_covers(
+            &et::meter_metrics(),
+            METER_DATA_START,
+            METER_DATA_COUNT,
+            &[],
+        );
+    }
+
+    /// Every value a snapshot decodes has to be a metric of the set with
+    /// the same first register and width
+    fn assert_matches_metrics(
+        ms: &MetricSet,
+        block: &Block,
+        decode: impl Fn(&Block) -> Result<(), MetricReadError>,
+    ) {
+        decode(block).unwrap();
+        let metrics: Vec<(u16, u16)> = ms
+            .metrics
+            .iter()
+            .map(|m| (m.get_register(), m.get_width()))
+            .collect();
+        let missing: Vec<(u16, u16)> = block
+            .reads
+            .borrow()
+            .iter()
+            .copied()
+            .filter(|read| !metrics.contains(read))
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "registers and widths without metric: {missing:?}"
+        );
+    }
+
+    #[test]
+    fn snapshots_match_metric_definitions() {
+        let data = vec![0; 2 * RUNNING_DATA_COUNT as usize];
+        assert_matches_metrics(
+            &et::base_metrics(),
+            &Block::new(RUNNING_DATA_START, &data),
+            |block| RunningData::decode_block(block).map(|_| ()),
+        );
+        assert_matches_metrics(
+            &et::battery_metrics(),
+            &Block::new(BMS_DATA_START, &data),
+            |block| BmsData::decode_block(block).map(|_| ()),
+        );
+        assert_matches_metrics(
+            &et::meter_metrics(),
+            &Block::new(METER_DATA_START, &data),
+            |block| MeterData::decode_block(block).map(|_| ()),
+        );
+    }
+
+    #[test]
+    fn decode_running_data() {
+        let data = block_data(
+            RUNNING_DATA_START,
+            RUNNING_DATA_COUNT,
+            &[
+                (35111, &[3521, 42, 0, 1478]),
+                (35131, &[2318, 27, 4998, 0, 612]),
+                // The reserved word before the power is ignored
+                (35161, &[0x1234, (-150_i16) as u16]),
+                (35175, &[421]),
+                (35182, &[0xffff, 0xfc18]),
+                (35191, &[1, 0x0000]),
+                (35197, &[0, 5120]),
+                (35220, &[0x0001, 0x0200]),
+            ],
+        );
+        let running = RunningData::decode(&data).unwrap();
+
+        assert_eq!(
+            running.pv[2],
+            MpptReading {
+                voltage: 352.1,
+                current: 4.2,
+                power: 1478,
+            }
+        );
+        assert_eq!(
+            running.grid[2],
+            PhaseReading {
+                voltage: 231.8,
+                current: 2.7,
+                frequency: 49.98,
+                power: 612,
+            }
+        );
+        assert_eq!(running.backup[2].power, -150);
+        assert_eq!(running.temperatures.module, 42.1);
+        assert_eq!(running.battery.power, -1000);
+        assert_eq!(running.energy.pv_generation_total, 6553.6);
+        assert_eq!(running.operation_hours, 5120);
+        assert_eq!(running.diagnostic_status, 0x0001_0200);
+    }
+
+    #[test]
+    fn decode_bms_data() {
+        let data = block_data(
+            BMS_DATA_START,
+            BMS_DATA_COUNT,
+            &[
+                (37006, &[0x0004]),
+                (37007, &[87, 99]),
+                (37011, &[0x0100]),
+                (37022, &[33, 32]),
+            ],
+        );
+        let bms = BmsData::decode(&data).unwrap();
+
+        assert_eq!(bms.state_of_charge, 87);
+        assert_eq!(bms.state_of_health, 99);
+        assert_eq!(bms.errors, 0x0100_0004);
+        assert_eq!(bms.max_cell_voltage, 3.3);
+    }
+
+    #[test]
+    fn decode_meter_data() {
+        let export = 1234.5_f32.to_bits();
+        let data = block_data(
+            METER_DATA_START,
+            METER_DATA_COUNT,
+            &[
+                (36011, &[990]),
+                (36015, &[(export >> 16) as u16, export as u16]),
+                (36021, &[0xffff, 0xff38]),
+                (36041, &[0, 4100]),
+                (36056, &[123]),
+            ],
+        );
+        let meter = MeterData::decode(&data).unwrap();
+
+        assert_eq!(meter.phases[1].active_power, -200);
+        assert_eq!(meter.phases[1].power_factor, 0.99);
+        assert_eq!(meter.total.apparent_power, 4100);
+        assert_eq!(meter.export_energy, 1234.5);
+        assert_eq!(meter.currents[1], 12.3);
+    }
+
+    #[test]
+    fn decode_rejects_short_blocks() {
+        let data = block_data(BMS_DATA_START, BMS_DATA_COUNT - 1, &[]);
+        assert!(matches!(
+            BmsData::decode(&data),
+            Err(MetricReadError::OutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn snapshot_survives_serde() {
+        let data = block_data(BMS_DATA_START, BMS_DATA_COUNT, &[(37007, &[55])]);
+        let bms = BmsData::decode(&data).unwrap();
+
+        let json = serde_json::to_string(&bms).unwrap();
+        assert!(json.contains("\"state_of_charge\":55"));
+        assert_eq!(serde_json::from_str::<BmsData>(&json).unwrap(), bms);
+    }
+}
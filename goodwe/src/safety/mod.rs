@@ -0,0 +1,261 @@
+use std::{
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::settings::{
+    battery::{
+        MAX_DEPTH_OF_DISCHARGE, REGISTER_CHARGE_CURRENT, REGISTER_DISCHARGE_CURRENT,
+        REGISTER_DOD_OFF_GRID, REGISTER_DOD_ON_GRID, REGISTER_SOC_RESERVE,
+    },
+    eco_schedule::{REGISTER_ECO_MODE_SLOTS, SLOT_COUNT},
+    export_limit::{REGISTER_EXPORT_ENABLED, REGISTER_EXPORT_LIMIT},
+    work_mode::REGISTER_WORK_MODE,
+};
+
+/// A register that may be written, and the range of values it accepts
+pub struct WritableRegister {
+    pub register: u16,
+    pub name: &'static str,
+    pub min: i32,
+    pub max: i32,
+    /// The register holds a two's complement value
+    pub signed: bool,
+}
+
+const fn writable(register: u16, name: &'static str, min: i32, max: i32) -> WritableRegister {
+    WritableRegister {
+        register,
+        name,
+        min,
+        max,
+        signed: false,
+    }
+}
+
+/// Every register a command is allowed to write. Anything else is refused
+/// before a frame is sent, so a wrong register number can't change
+/// something unrelated on the inverter.
+pub const WRITABLE_REGISTERS: &[WritableRegister] = &[
+    writable(REGISTER_CHARGE_CURRENT, "maximum charge current", 0, 1000),
+    writable(
+        REGISTER_DISCHARGE_CURRENT,
+        "maximum discharge current",
+        0,
+        1000,
+    ),
+    writable(
+        REGISTER_DOD_ON_GRID,
+        "on-grid depth of discharge",
+        0,
+        MAX_DEPTH_OF_DISCHARGE as i32,
+    ),
+    writable(
+        REGISTER_DOD_OFF_GRID,
+        "off-grid depth of discharge",
+        0,
+        MAX_DEPTH_OF_DISCHARGE as i32,
+    ),
+    writable(REGISTER_WORK_MODE, "work mode", 0, 3),
+    writable(REGISTER_EXPORT_ENABLED, "export limit enabled", 0, 1),
+    writable(REGISTER_EXPORT_LIMIT, "export limit", 0, 30000),
+    writable(REGISTER_SOC_RESERVE, "SoC reserve", 0, 100),
+];
+
+/// The registers of a single eco mode slot, relative to its first register.
+/// All slots share the same layout.
+pub const ECO_SLOT_REGISTERS: &[WritableRegister] = &[
+    writable(0, "eco slot start", 0, 0x173b),
+    writable(1, "eco slot end", 0, 0x173b),
+    writable(2, "eco slot weekdays", 0, 0xff7f),
+    writable(3, "eco slot months", 0, 0x0fff),
+    WritableRegister {
+        register: 4,
+        name: "eco slot power",
+        min: -100,
+        max: 100,
+        signed: true,
+    },
+    writable(5, "eco slot SoC", 0, 100),
+];
+
+pub fn writable_register(register: u16) -> Option<&'static WritableRegister> {
+    let eco_registers = SLOT_COUNT * ECO_SLOT_REGISTERS.len();
+    match register.checked_sub(REGISTER_ECO_MODE_SLOTS) {
+        Some(offset) if (offset as usize) < eco_registers => {
+            Some(&ECO_SLOT_REGISTERS[offset as usize % ECO_SLOT_REGISTERS.len()])
+        }
+        _ => WRITABLE_REGISTERS.iter().find(|r| r.register == register),
+    }
+}
+
+impl WritableRegister {
+    pub fn to_value(&self, raw: u16) -> i32 {
+        if self.signed {
+            raw as i16 as i32
+        } else {
+            raw as i32
+        }
+    }
+
+    pub fn allows(&self, raw: u16) -> bool {
+        (self.min..=self.max).contains(&self.to_value(raw))
+    }
+}
+
+/// Check that `value` may be written to `register`
+pub fn check_write(register: u16, value: u16) -> bool {
+    writable_register(register).is_some_and(|r| r.allows(value))
+}
+
+/// A single register about to be changed
+#[derive(Clone, Copy)]
+pub struct RegisterChange {
+    pub register: u16,
+    pub old: u16,
+    pub new: u16,
+}
+
+impl Display for RegisterChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match writable_register(self.register) {
+            Some(r) => write!(
+                f,
+                "{} ({}): {} -> {}",
+                self.register,
+                r.name,
+                r.to_value(self.old),
+                r.to_value(self.new)
+            ),
+            None => write!(f, "{}: {} -> {}", self.register, self.old, self.new),
+        }
+    }
+}
+
+/// How far a write recorded in the audit log got
+pub enum WriteStatus {
+    /// About to be sent to the inverter
+    Sending,
+    /// Acknowledged and, if requested, verified
+    Applied,
+    /// Not acknowledged or not verified, the registers may or may not have
+    /// changed
+    Failed(String),
+}
+
+impl Display for WriteStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteStatus::Sending => write!(f, "status=sending"),
+            WriteStatus::Applied => write!(f, "status=applied"),
+            WriteStatus::Failed(error) => write!(f, "status=failed error={error:?}"),
+        }
+    }
+}
+
+/// Append-only record of every register write sent to an inverter. The file
+/// is only created once the first write is recorded.
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl AuditLog {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            file: Mutex::new(None),
+        }
+    }
+
+    pub fn record(
+        &self,
+        target: &str,
+        changes: &[RegisterChange],
+        status: &WriteStatus,
+    ) -> io::Result<()> {
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        let mut lines = String::new();
+        for change in changes {
+            lines.push_str(&format!(
+                "{timestamp} target={target} register={} old={} new={} {status}\n",
+                change.register, change.old, change.new
+            ));
+        }
+
+        // A single write per call keeps the lines of concurrent writers apart
+        let mut file = self.file.lock().unwrap();
+        let file = match &mut *file {
+            Some(file) => file,
+            None => {
+                let opened = OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(&self.path)
+                    .map_err(|e| {
+                        io::Error::new(e.kind(), format!("{}: {e}", self.path.display()))
+                    })?;
+                file.insert(opened)
+            }
+        };
+        file.write_all(lines.as_bytes())?;
+        file.flush()
+    }
+}
+
+/// The changes and the request frame of a write that was skipped because of
+/// a dry run
+pub struct PlannedWrite {
+    pub changes: Vec<RegisterChange>,
+    pub frame: Vec<u8>,
+}
+
+/// Asked with the pending changes before anything is written, a write only
+/// happens if it returns true
+pub type Confirmation = Arc<dyn Fn(&[RegisterChange]) -> bool + Send + Sync>;
+
+/// How a connection handles register writes
+#[derive(Clone, Default)]
+pub struct WriteOptions {
+    /// Only plan the writes instead of sending them, see
+    /// [`Connection::take_planned_writes`](crate::metrics::connection::Connection::take_planned_writes)
+    pub dry_run: bool,
+    pub confirm: Option<Confirmation>,
+    pub audit_log: Option<Arc<AuditLog>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_write_refuses_unknown_registers() {
+        assert!(!check_write(REGISTER_WORK_MODE + 1, 0));
+        assert!(!check_write(REGISTER_ECO_MODE_SLOTS - 1, 0));
+        assert!(!check_write(REGISTER_ECO_MODE_SLOTS + 24, 0));
+    }
+
+    #[test]
+    fn check_write_enforces_ranges() {
+        assert!(check_write(REGISTER_WORK_MODE, 3));
+        assert!(!check_write(REGISTER_WORK_MODE, 4));
+        assert!(check_write(REGISTER_DOD_ON_GRID, MAX_DEPTH_OF_DISCHARGE));
+        assert!(!check_write(
+            REGISTER_DOD_ON_GRID,
+            MAX_DEPTH_OF_DISCHARGE + 1
+        ));
+    }
+
+    #[test]
+    fn check_write_maps_every_eco_slot() {
+        let power = |slot: u16| REGISTER_ECO_MODE_SLOTS + slot * 6 + 4;
+        for slot in 0..SLOT_COUNT as u16 {
+            assert!(check_write(power(slot), -100_i16 as u16));
+            assert!(!check_write(power(slot), -101_i16 as u16));
+            assert!(!check_write(power(slot), 101));
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::BTreeMap, error::Error, fmt::Display, fs, io, path::Path, thread, time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::{
+    connection::Connection,
+    modbus::{self, ExceptionCode, ModbusError},
+    MetricsError,
+};
+
+/// What reading a single register returned
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegisterState {
+    Value(u16),
+    /// The inverter does not know the register
+    IllegalAddress,
+    /// Reading failed for another reason, e.g. a timeout
+    Failed(String),
+}
+
+impl Display for RegisterState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegisterState::Value(value) => write!(f, "{value:#06x} ({value}, {})", *value as i16),
+            RegisterState::IllegalAddress => write!(f, "illegal address"),
+            RegisterState::Failed(reason) => write!(f, "failed: {reason}"),
+        }
+    }
+}
+
+/// All registers of a scan, saved as JSON
+#[derive(Serialize, Deserialize)]
+pub struct ScanDump {
+    pub target: String,
+    /// When the scan started, in RFC 3339 format
+    pub timestamp: String,
+    pub registers: BTreeMap<u16, RegisterState>,
+}
+
+pub struct ScanOptions {
+    /// Registers read with a single request
+    pub chunk_size: u16,
+    /// Pause between two requests, to not keep the inverter too busy
+    pub delay: Duration,
+}
+
+/// A register that differs between two dumps
+pub struct RegisterDiff {
+    pub register: u16,
+    /// `None` if the register is not part of the dump
+    pub before: Option<RegisterState>,
+    pub after: Option<RegisterState>,
+}
+
+#[derive(Debug)]
+pub enum DumpError {
+    IoError(io::Error),
+    FormatError(serde_json::Error),
+}
+
+impl Display for DumpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DumpError::IoError(e) => write!(f, "Unable to access dump: {e}"),
+            DumpError::FormatError(e) => write!(f, "Invalid dump: {e}"),
+        }
+    }
+}
+
+impl Error for DumpError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DumpError::IoError(e) => Some(e),
+            DumpError::FormatError(e) => Some(e),
+        }
+    }
+}
+
+impl ScanDump {
+    pub fn load(path: &Path) -> Result<Self, DumpError> {
+        let content = fs::read_to_string(path).map_err(DumpError::IoError)?;
+        serde_json::from_str(&content).map_err(DumpError::FormatError)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), DumpError> {
+        let content = serde_json::to_string_pretty(self).map_err(DumpError::FormatError)?;
+        fs::write(path, content).map_err(DumpError::IoError)
+    }
+
+    /// Registers whose state differs in `other`, including those only one of
+    /// the dumps contains
+    pub fn diff(&self, other: &ScanDump) -> Vec<RegisterDiff> {
+        let mut registers: Vec<u16> = self
+            .registers
+            .keys()
+            .chain(other.registers.keys())
+            .copied()
+            .collect();
+        registers.sort_unstable();
+        registers.dedup();
+
+        registers
+            .into_iter()
+            .filter_map(|register| {
+                let before = self.registers.get(&register);
+                let after = other.registers.get(&register);
+                (before != after).then(|| RegisterDiff {
+                    register,
+                    before: before.cloned(),
+                    after: after.cloned(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Read `count` registers from `start` on in chunks. If the inverter refuses
+/// a chunk because it contains unknown registers, its registers are read one
+/// by one to find out which of them exist.
+pub fn scan(
+    conn: &Connection,
+    start: u16,
+    count: u16,
+    options: &ScanOptions,
+    mut progress: impl FnMut(u16, u16),
+) -> ScanDump {
+    let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let mut registers = BTreeMap::new();
+    let end = start as u32 + count as u32;
+    let chunk_size = options.chunk_size.clamp(1, modbus::MAX_READ_REGISTERS);
+
+    let mut chunk_start = start as u32;
+    while chunk_start < end {
+        let chunk_count = (end - chunk_start).min(chunk_size as u32) as u16;
+        let chunk_start_register = chunk_start as u16;
+        progress(chunk_start_register, chunk_count);
+
+        match conn.read_registers(chunk_start_register, chunk_count) {
+            Ok(data) => {
+                for (offset, value) in modbus::to_registers(&data).into_iter().enumerate() {
+                    registers.insert(
+                        chunk_start_register + offset as u16,
+                        RegisterState::Value(value),
+                    );
+                }
+            }
+            Err(MetricsError::ModbusError(ModbusError::Exception(
+                ExceptionCode::IllegalDataAddress,
+            ))) if chunk_count > 1 => {
+                for register in (chunk_start_register..=u16::MAX).take(chunk_count as usize) {
+                    thread::sleep(options.delay);
+                    registers.insert(register, read_single(conn, register));
+                }
+            }
+            Err(e) => {
+                let state = to_state(e);
+                for register in (chunk_start_register..=u16::MAX).take(chunk_count as usize) {
+                    registers.insert(register, state.clone());
+                }
+            }
+        }
+
+        chunk_start += chunk_count as u32;
+        thread::sleep(options.delay);
+    }
+
+    ScanDump {
+        target: conn.target().to_owned(),
+        timestamp,
+        registers,
+    }
+}
+
+fn read_single(conn: &Connection, register: u16) -> RegisterState {
+    match conn.read_registers(register, 1) {
+        Ok(data) => RegisterState::Value(modbus::to_registers(&data)[0]),
+        Err(e) => to_state(e),
+    }
+}
+
+fn to_state(e: MetricsError) -> RegisterState {
+    match e {
+        MetricsError::ModbusError(ModbusError::Exception(ExceptionCode::IllegalDataAddress)) => {
+            RegisterState::IllegalAddress
+        }
+        e => RegisterState::Failed(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(registers: &[(u16, RegisterState)]) -> ScanDump {
+        ScanDump {
+            target: "192.168.1.50".to_owned(),
+            timestamp: "2024-01-01T00:00:00Z".to_owned(),
+            registers: registers.iter().cloned().collect(),
+        }
+    }
+
+    #[test]
+    fn diff_reports_changed_and_missing_registers() {
+        let before = dump(&[
+            (100, RegisterState::Value(1)),
+            (101, RegisterState::Value(2)),
+            (102, RegisterState::IllegalAddress),
+        ]);
+        let after = dump(&[
+            (101, RegisterState::Value(3)),
+            (102, RegisterState::IllegalAddress),
+            (103, RegisterState::Value(4)),
+        ]);
+
+        let diff: Vec<(u16, Option<RegisterState>, Option<RegisterState>)> = before
+            .diff(&after)
+            .into_iter()
+            .map(|d| (d.register, d.before, d.after))
+            .collect();
+        assert_eq!(
+            diff,
+            vec![
+                (100, Some(RegisterState::Value(1)), None),
+                (
+                    101,
+                    Some(RegisterState::Value(2)),
+                    Some(RegisterState::Value(3))
+                ),
+                (103, None, Some(RegisterState::Value(4))),
+            ]
+        );
+    }
+
+    #[test]
+    fn dump_round_trips_through_json() {
+        let original = dump(&[
+            (35100, RegisterState::Value(0xffff)),
+            (35101, RegisterState::IllegalAddress),
+            (
+                35102,
+                RegisterState::Failed("Network Error: timed out".to_owned()),
+            ),
+        ]);
+        let json = serde_json::to_string(&original).unwrap();
+        let loaded: ScanDump = serde_json::from_str(&json).unwrap();
+        assert!(original.diff(&loaded).is_empty());
+    }
+}
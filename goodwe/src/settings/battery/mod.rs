@@ -0,0 +1,284 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use super::SettingsError;
+use crate::metrics::{connection::Connection, modbus};
+
+pub const REGISTER_CHARGE_CURRENT: u16 = 45353;
+pub const REGISTER_DISCHARGE_CURRENT: u16 = 45355;
+pub const REGISTER_DOD_ON_GRID: u16 = 45356;
+pub const REGISTER_DOD_OFF_GRID: u16 = 45358;
+pub const REGISTER_SOC_RESERVE: u16 = 47602;
+
+/// Charge and discharge current limits reported by the BMS
+pub const REGISTER_BMS_CURRENT_LIMITS: u16 = 37004;
+
+/// The inverter always keeps a minimum charge in the battery
+pub const MAX_DEPTH_OF_DISCHARGE: u16 = 95;
+
+/// Lowest SoC reserve in %, the minimum charge the inverter keeps anyway
+pub const MIN_SOC_RESERVE: u16 = 100 - MAX_DEPTH_OF_DISCHARGE;
+
+#[derive(Serialize)]
+pub struct BatterySettings {
+    /// Depth of discharge in % while connected to the grid
+    pub depth_of_discharge_on_grid: u16,
+    /// Depth of discharge in % while off grid
+    pub depth_of_discharge_off_grid: u16,
+    /// Maximum charge current in A
+    pub max_charge_current: f32,
+    /// Maximum discharge current in A
+    pub max_discharge_current: f32,
+    /// State of charge in % kept in reserve for backup operation
+    pub soc_reserve: u16,
+}
+
+/// Current limits in A the battery management system allows
+pub struct BmsLimits {
+    pub charge_current: u16,
+    pub discharge_current: u16,
+}
+
+impl BmsLimits {
+    /// Without a BMS reporting limits there is no battery to configure
+    pub fn battery_present(&self) -> bool {
+        self.charge_current > 0 || self.discharge_current > 0
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum BatterySetting {
+    DepthOfDischargeOnGrid(u16),
+    DepthOfDischargeOffGrid(u16),
+    MaxChargeCurrent(f32),
+    MaxDischargeCurrent(f32),
+    SocReserve(u16),
+}
+
+impl BatterySetting {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatterySetting::DepthOfDischargeOnGrid(_) => "on-grid depth of discharge",
+            BatterySetting::DepthOfDischargeOffGrid(_) => "off-grid depth of discharge",
+            BatterySetting::MaxChargeCurrent(_) => "maximum charge current",
+            BatterySetting::MaxDischargeCurrent(_) => "maximum discharge current",
+            BatterySetting::SocReserve(_) => "SoC reserve",
+        }
+    }
+
+    fn register(&self) -> u16 {
+        match self {
+            BatterySetting::DepthOfDischargeOnGrid(_) => REGISTER_DOD_ON_GRID,
+            BatterySetting::DepthOfDischargeOffGrid(_) => REGISTER_DOD_OFF_GRID,
+            BatterySetting::MaxChargeCurrent(_) => REGISTER_CHARGE_CURRENT,
+            BatterySetting::MaxDischargeCurrent(_) => REGISTER_DISCHARGE_CURRENT,
+            BatterySetting::SocReserve(_) => REGISTER_SOC_RESERVE,
+        }
+    }
+
+    /// Register value, currents are stored in steps of 0.1 A
+    fn register_value(&self) -> Result<u16, SettingsError> {
+        match *self {
+            BatterySetting::DepthOfDischargeOnGrid(value)
+            | BatterySetting::DepthOfDischargeOffGrid(value)
+            | BatterySetting::SocReserve(value) => Ok(value),
+            BatterySetting::MaxChargeCurrent(value)
+            | BatterySetting::MaxDischargeCurrent(value) => {
+                let steps = (value * 10.0).round();
+                if !(0.0..=u16::MAX as f32).contains(&steps) {
+                    return Err(SettingsError::OutOfRange {
+                        setting: self.name(),
+                        value,
+                        min: 0.0,
+                        max: u16::MAX as f32 / 10.0,
+                    });
+                }
+                Ok(steps as u16)
+            }
+        }
+    }
+
+    /// Check the value against the valid range, taking the current limits of
+    /// the BMS into account. Nothing can be set while no BMS reports limits.
+    pub fn validate(&self, limits: &BmsLimits) -> Result<(), SettingsError> {
+        if !limits.battery_present() {
+            return Err(SettingsError::NoBattery);
+        }
+
+        let (value, min, max) = match *self {
+            BatterySetting::DepthOfDischargeOnGrid(value)
+            | BatterySetting::DepthOfDischargeOffGrid(value) => {
+                (value as f32, 0.0, MAX_DEPTH_OF_DISCHARGE as f32)
+            }
+            BatterySetting::SocReserve(value) => (value as f32, MIN_SOC_RESERVE as f32, 100.0),
+            BatterySetting::MaxChargeCurrent(value) => (value, 0.0, limits.charge_current as f32),
+            BatterySetting::MaxDischargeCurrent(value) => {
+                (value, 0.0, limits.discharge_current as f32)
+            }
+        };
+
+        if !(min..=max).contains(&value) {
+            return Err(SettingsError::OutOfRange {
+                setting: self.name(),
+                value,
+                min,
+                max,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Display for BatterySettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            " - Depth of Discharge (on-grid): {} %",
+            self.depth_of_discharge_on_grid
+        )?;
+        writeln!(
+            f,
+            " - Depth of Discharge (off-grid): {} %",
+            self.depth_of_discharge_off_grid
+        )?;
+        writeln!(
+            f,
+            " - Maximum Charge Current: {} A",
+            self.max_charge_current
+        )?;
+        writeln!(
+            f,
+            " - Maximum Discharge Current: {} A",
+            self.max_discharge_current
+        )?;
+        write!(f, " - SoC Reserve: {} %", self.soc_reserve)
+    }
+}
+
+pub fn get_battery_settings(conn: &Connection) -> Result<BatterySettings, SettingsError> {
+    let count = REGISTER_DOD_OFF_GRID - REGISTER_CHARGE_CURRENT + 1;
+    let registers = modbus::to_registers(&conn.read_registers(REGISTER_CHARGE_CURRENT, count)?);
+    let register = |reg: u16| registers[(reg - REGISTER_CHARGE_CURRENT) as usize];
+    let soc_reserve = modbus::to_registers(&conn.read_registers(REGISTER_SOC_RESERVE, 1)?)[0];
+
+    Ok(BatterySettings {
+        depth_of_discharge_on_grid: register(REGISTER_DOD_ON_GRID),
+        depth_of_discharge_off_grid: register(REGISTER_DOD_OFF_GRID),
+        max_charge_current: register(REGISTER_CHARGE_CURRENT) as f32 / 10.0,
+        max_discharge_current: register(REGISTER_DISCHARGE_CURRENT) as f32 / 10.0,
+        soc_reserve,
+    })
+}
+
+pub fn get_bms_limits(conn: &Connection) -> Result<BmsLimits, SettingsError> {
+    let registers = modbus::to_registers(&conn.read_registers(REGISTER_BMS_CURRENT_LIMITS, 2)?);
+
+    Ok(BmsLimits {
+        charge_current: registers[0],
+        discharge_current: registers[1],
+    })
+}
+
+/// Validate all settings against the BMS limits, and only if all of them are
+/// valid write and verify them one after the other. They are confirmed
+/// together, as a single change.
+pub fn set_battery_settings(
+    conn: &Connection,
+    settings: &[BatterySetting],
+) -> Result<(), SettingsError> {
+    let limits = get_bms_limits(conn)?;
+    for setting in settings {
+        setting.validate(&limits)?;
+    }
+
+    let values = settings
+        .iter()
+        .map(|setting| setting.register_value())
+        .collect::<Result<Vec<u16>, SettingsError>>()?;
+
+    let writes: Vec<(u16, &[u16])> = settings
+        .iter()
+        .zip(&values)
+        .map(|(setting, value)| (setting.register(), std::slice::from_ref(value)))
+        .collect();
+    conn.write_batch(&writes, true)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: BmsLimits = BmsLimits {
+        charge_current: 50,
+        discharge_current: 40,
+    };
+
+    fn out_of_range(setting: BatterySetting, limits: &BmsLimits) -> Option<(f32, f32)> {
+        match setting.validate(limits) {
+            Err(SettingsError::OutOfRange { min, max, .. }) => Some((min, max)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn currents_are_limited_by_the_bms() {
+        assert!(BatterySetting::MaxChargeCurrent(50.0)
+            .validate(&LIMITS)
+            .is_ok());
+        assert_eq!(
+            out_of_range(BatterySetting::MaxDischargeCurrent(40.5), &LIMITS),
+            Some((0.0, 40.0))
+        );
+        assert_eq!(
+            out_of_range(BatterySetting::MaxChargeCurrent(-1.0), &LIMITS),
+            Some((0.0, 50.0))
+        );
+    }
+
+    #[test]
+    fn depth_of_discharge_and_reserve_keep_minimum_charge() {
+        assert!(BatterySetting::DepthOfDischargeOnGrid(95)
+            .validate(&LIMITS)
+            .is_ok());
+        assert_eq!(
+            out_of_range(BatterySetting::DepthOfDischargeOffGrid(96), &LIMITS),
+            Some((0.0, 95.0))
+        );
+        assert!(BatterySetting::SocReserve(5).validate(&LIMITS).is_ok());
+        assert_eq!(
+            out_of_range(BatterySetting::SocReserve(4), &LIMITS),
+            Some((5.0, 100.0))
+        );
+    }
+
+    #[test]
+    fn nothing_is_valid_without_a_battery() {
+        let limits = BmsLimits {
+            charge_current: 0,
+            discharge_current: 0,
+        };
+        assert!(matches!(
+            BatterySetting::SocReserve(10).validate(&limits),
+            Err(SettingsError::NoBattery)
+        ));
+    }
+
+    #[test]
+    fn register_values_do_not_saturate() {
+        assert_eq!(
+            BatterySetting::MaxChargeCurrent(25.04)
+                .register_value()
+                .unwrap(),
+            250
+        );
+        assert!(BatterySetting::MaxChargeCurrent(7000.0)
+            .register_value()
+            .is_err());
+        assert!(BatterySetting::MaxDischargeCurrent(f32::NAN)
+            .register_value()
+            .is_err());
+    }
+}
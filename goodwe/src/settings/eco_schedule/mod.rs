@@ -0,0 +1,541 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::SettingsError;
+use crate::metrics::{connection::Connection, modbus};
+
+/// First register of the eco mode slots
+pub const REGISTER_ECO_MODE_SLOTS: u16 = 47547;
+pub const SLOT_COUNT: usize = 4;
+/// Registers per slot: start, end, enabled and weekdays, months, power, SoC
+const SLOT_REGISTERS: usize = 6;
+
+const MINUTES_PER_DAY: usize = 24 * 60;
+
+/// Eco mode charge and discharge windows. The slots only take effect while
+/// the inverter runs in eco mode.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(try_from = "ScheduleDocument", into = "ScheduleDocument")]
+pub struct EcoSchedule {
+    /// One entry per slot of the inverter, `None` for disabled slots. Slots
+    /// past the end of the list are disabled as well, schedules read from
+    /// the inverter or a document always have an entry for every slot.
+    pub slots: Vec<Option<EcoSlot>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EcoSlot {
+    pub start: TimeOfDay,
+    /// A slot ending before it starts continues on the next day
+    pub end: TimeOfDay,
+    pub days: Vec<Weekday>,
+    pub mode: EcoAction,
+    /// Charge or discharge power in % of the rated power
+    pub power: u16,
+    /// Charging stops at, discharging stops below this state of charge in %
+    pub soc: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Sun,
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EcoAction {
+    Charge,
+    Discharge,
+}
+
+/// Schedule as written in TOML, which has no way to leave out entries of an
+/// array. Every enabled slot carries its number instead.
+#[derive(Serialize, Deserialize)]
+struct ScheduleDocument {
+    #[serde(default, rename = "slot")]
+    slots: Vec<SlotEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SlotEntry {
+    /// Counted from 1, entries without number take the slots in order
+    #[serde(rename = "slot", default, skip_serializing_if = "Option::is_none")]
+    number: Option<usize>,
+    start: TimeOfDay,
+    end: TimeOfDay,
+    days: Vec<Weekday>,
+    mode: EcoAction,
+    power: u16,
+    soc: u16,
+}
+
+impl TryFrom<ScheduleDocument> for EcoSchedule {
+    type Error = String;
+
+    fn try_from(document: ScheduleDocument) -> Result<Self, Self::Error> {
+        let mut slots: Vec<Option<EcoSlot>> = vec![None; SLOT_COUNT];
+        for (index, entry) in document.slots.into_iter().enumerate() {
+            let number = entry.number.unwrap_or(index + 1);
+            if !(1..=SLOT_COUNT).contains(&number) {
+                return Err(format!(
+                    "slot {number} does not exist, there are {SLOT_COUNT}"
+                ));
+            }
+            if slots[number - 1].is_some() {
+                return Err(format!("slot {number} is given twice"));
+            }
+            slots[number - 1] = Some(EcoSlot {
+                start: entry.start,
+                end: entry.end,
+                days: entry.days,
+                mode: entry.mode,
+                power: entry.power,
+                soc: entry.soc,
+            });
+        }
+        Ok(EcoSchedule { slots })
+    }
+}
+
+impl From<EcoSchedule> for ScheduleDocument {
+    fn from(schedule: EcoSchedule) -> Self {
+        let slots = schedule
+            .slots
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                slot.map(|slot| SlotEntry {
+                    number: Some(index + 1),
+                    start: slot.start,
+                    end: slot.end,
+                    days: slot.days,
+                    mode: slot.mode,
+                    power: slot.power,
+                    soc: slot.soc,
+                })
+            })
+            .collect();
+        ScheduleDocument { slots }
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid time '{value}', expected HH:MM");
+        let (hour, minute) = value.split_once(':').ok_or_else(invalid)?;
+        let hour: u8 = hour.parse().map_err(|_| invalid())?;
+        let minute: u8 = minute.parse().map_err(|_| invalid())?;
+        if hour > 23 || minute > 59 {
+            return Err(invalid());
+        }
+        Ok(TimeOfDay { hour, minute })
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(value: TimeOfDay) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for TimeOfDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+impl TimeOfDay {
+    fn minutes(&self) -> usize {
+        self.hour as usize * 60 + self.minute as usize
+    }
+
+    fn to_register(self) -> u16 {
+        (self.hour as u16) << 8 | self.minute as u16
+    }
+
+    fn from_register(value: u16) -> Result<Self, SettingsError> {
+        let hour = (value >> 8) as u8;
+        let minute = (value & 0xff) as u8;
+        if hour > 23 || minute > 59 {
+            return Err(SettingsError::UnknownValue {
+                setting: "eco mode slot time",
+                value,
+            });
+        }
+        Ok(TimeOfDay { hour, minute })
+    }
+}
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Sun,
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Weekday::Sun => "sun",
+            Weekday::Mon => "mon",
+            Weekday::Tue => "tue",
+            Weekday::Wed => "wed",
+            Weekday::Thu => "thu",
+            Weekday::Fri => "fri",
+            Weekday::Sat => "sat",
+        }
+    }
+}
+
+impl Display for EcoSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let days: Vec<&str> = self.days.iter().map(|d| d.name()).collect();
+        let mode = match self.mode {
+            EcoAction::Charge => "charge",
+            EcoAction::Discharge => "discharge",
+        };
+        write!(
+            f,
+            "{}-{} {} {mode} at {}% until {}% SoC",
+            self.start,
+            self.end,
+            days.join(","),
+            self.power,
+            self.soc
+        )
+    }
+}
+
+impl EcoSlot {
+    fn to_registers(&self) -> [u16; SLOT_REGISTERS] {
+        let day_bits = self
+            .days
+            .iter()
+            .fold(0_u16, |bits, day| bits | 1 << (*day as u16));
+        let power = match self.mode {
+            EcoAction::Charge => -(self.power as i16),
+            EcoAction::Discharge => self.power as i16,
+        };
+
+        [
+            self.start.to_register(),
+            self.end.to_register(),
+            0xff00 | day_bits,
+            // No restriction to certain months
+            0,
+            power as u16,
+            self.soc,
+        ]
+    }
+
+    /// The slot if it is enabled. Times of disabled slots aren't checked,
+    /// the inverter leaves anything in there.
+    fn from_registers(registers: &[u16]) -> Result<Option<Self>, SettingsError> {
+        let enabled = registers[2] >> 8 != 0;
+        let days: Vec<Weekday> = Weekday::ALL
+            .into_iter()
+            .filter(|day| registers[2] & (1 << (*day as u16)) != 0)
+            .collect();
+        if !enabled || days.is_empty() {
+            return Ok(None);
+        }
+
+        let power = registers[4] as i16;
+        Ok(Some(EcoSlot {
+            start: TimeOfDay::from_register(registers[0])?,
+            end: TimeOfDay::from_register(registers[1])?,
+            days,
+            mode: if power < 0 {
+                EcoAction::Charge
+            } else {
+                EcoAction::Discharge
+            },
+            power: power.unsigned_abs(),
+            soc: registers[5],
+        }))
+    }
+
+    /// Minutes of the week covered by the slot
+    fn minutes_of_week(&self) -> Vec<bool> {
+        let week = 7 * MINUTES_PER_DAY;
+        let mut minutes = vec![false; week];
+        let start = self.start.minutes();
+        let mut duration = (self.end.minutes() + MINUTES_PER_DAY - start) % MINUTES_PER_DAY;
+        if duration == 0 {
+            duration = MINUTES_PER_DAY;
+        }
+
+        for day in &self.days {
+            let first = *day as usize * MINUTES_PER_DAY + start;
+            for minute in first..(first + duration) {
+                minutes[minute % week] = true;
+            }
+        }
+        minutes
+    }
+}
+
+impl EcoSchedule {
+    /// The slot with the given index counted from 0, if it is enabled
+    pub fn slot(&self, index: usize) -> Option<&EcoSlot> {
+        self.slots.get(index).and_then(Option::as_ref)
+    }
+
+    fn enabled_slots(&self) -> impl Iterator<Item = (usize, &EcoSlot)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.as_ref().map(|slot| (index, slot)))
+    }
+
+    /// Check slot count, value ranges and that no two slots overlap
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let invalid = |reason: String| Err(SettingsError::InvalidSchedule(reason));
+
+        if self.slots.len() > SLOT_COUNT {
+            return invalid(format!("At most {SLOT_COUNT} slots are supported"));
+        }
+
+        for (index, slot) in self.enabled_slots() {
+            let number = index + 1;
+            if slot.days.is_empty() {
+                return invalid(format!("Slot {number} is not active on any day"));
+            }
+            if !(1..=100).contains(&slot.power) {
+                return invalid(format!(
+                    "Power of slot {number} has to be between 1 and 100%"
+                ));
+            }
+            if slot.soc > 100 {
+                return invalid(format!("SoC of slot {number} has to be at most 100%"));
+            }
+        }
+
+        let minutes: Vec<(usize, Vec<bool>)> = self
+            .enabled_slots()
+            .map(|(index, slot)| (index, slot.minutes_of_week()))
+            .collect();
+        for (position, (first, first_minutes)) in minutes.iter().enumerate() {
+            for (second, second_minutes) in minutes.iter().skip(position + 1) {
+                if first_minutes
+                    .iter()
+                    .zip(second_minutes)
+                    .any(|(a, b)| *a && *b)
+                {
+                    return invalid(format!("Slots {} and {} overlap", first + 1, second + 1));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lines describing how `other` differs from this schedule, slot by slot
+    pub fn diff(&self, other: &EcoSchedule) -> Vec<String> {
+        let mut lines = Vec::new();
+        for index in 0..SLOT_COUNT {
+            let current = self.slot(index);
+            let requested = other.slot(index);
+            if current == requested {
+                continue;
+            }
+            let number = index + 1;
+            match current {
+                Some(slot) => lines.push(format!("- slot {number}: {slot}")),
+                None => lines.push(format!("- slot {number}: disabled")),
+            }
+            match requested {
+                Some(slot) => lines.push(format!("+ slot {number}: {slot}")),
+                None => lines.push(format!("+ slot {number}: disabled")),
+            }
+        }
+        lines
+    }
+
+    fn to_registers(&self) -> Vec<u16> {
+        let mut registers = Vec::with_capacity(SLOT_COUNT * SLOT_REGISTERS);
+        for index in 0..SLOT_COUNT {
+            match self.slot(index) {
+                Some(slot) => registers.extend_from_slice(&slot.to_registers()),
+                None => registers.extend_from_slice(&[0; SLOT_REGISTERS]),
+            }
+        }
+        registers
+    }
+}
+
+pub fn get_eco_schedule(conn: &Connection) -> Result<EcoSchedule, SettingsError> {
+    let count = (SLOT_COUNT * SLOT_REGISTERS) as u16;
+    let registers = modbus::to_registers(&conn.read_registers(REGISTER_ECO_MODE_SLOTS, count)?);
+
+    Ok(EcoSchedule {
+        slots: registers
+            .chunks_exact(SLOT_REGISTERS)
+            .map(EcoSlot::from_registers)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Validate the schedule and write all slots, unused ones are disabled
+pub fn set_eco_schedule(conn: &Connection, schedule: &EcoSchedule) -> Result<(), SettingsError> {
+    schedule.validate()?;
+    conn.write_registers(REGISTER_ECO_MODE_SLOTS, &schedule.to_registers(), true)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(start: &str, end: &str, days: &[Weekday]) -> EcoSlot {
+        EcoSlot {
+            start: TimeOfDay::try_from(start.to_owned()).unwrap(),
+            end: TimeOfDay::try_from(end.to_owned()).unwrap(),
+            days: days.to_vec(),
+            mode: EcoAction::Charge,
+            power: 50,
+            soc: 90,
+        }
+    }
+
+    #[test]
+    fn validate_accepts_adjacent_slots() {
+        let schedule = EcoSchedule {
+            slots: vec![
+                Some(slot("01:00", "05:00", &[Weekday::Mon])),
+                Some(slot("05:00", "07:00", &[Weekday::Mon])),
+            ],
+        };
+        assert!(schedule.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_overlap_past_midnight() {
+        let schedule = EcoSchedule {
+            slots: vec![
+                Some(slot("22:00", "02:00", &[Weekday::Sat])),
+                None,
+                Some(slot("01:00", "05:00", &[Weekday::Sun])),
+            ],
+        };
+        assert!(matches!(
+            schedule.validate(),
+            Err(SettingsError::InvalidSchedule(reason)) if reason == "Slots 1 and 3 overlap"
+        ));
+    }
+
+    #[test]
+    fn registers_round_trip() {
+        let mut discharge = slot("17:30", "21:00", &[Weekday::Sun, Weekday::Fri]);
+        discharge.mode = EcoAction::Discharge;
+        let schedule = EcoSchedule {
+            slots: vec![
+                Some(slot("01:00", "05:00", &Weekday::ALL)),
+                None,
+                Some(discharge),
+                None,
+            ],
+        };
+
+        let registers = schedule.to_registers();
+        assert_eq!(registers.len(), SLOT_COUNT * SLOT_REGISTERS);
+        let decoded: Vec<Option<EcoSlot>> = registers
+            .chunks_exact(SLOT_REGISTERS)
+            .map(EcoSlot::from_registers)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(decoded, schedule.slots);
+    }
+
+    #[test]
+    fn disabled_slots_keep_their_place() {
+        let mut registers = [0_u16; SLOT_COUNT * SLOT_REGISTERS];
+        registers[SLOT_REGISTERS..2 * SLOT_REGISTERS]
+            .copy_from_slice(&slot("02:00", "04:00", &[Weekday::Tue]).to_registers());
+        let schedule = EcoSchedule {
+            slots: registers
+                .chunks_exact(SLOT_REGISTERS)
+                .map(EcoSlot::from_registers)
+                .collect::<Result<_, _>>()
+                .unwrap(),
+        };
+        assert_eq!(schedule.slot(0), None);
+        assert_eq!(schedule.slot(1).unwrap().start.hour, 2);
+
+        let document = toml::to_string(&schedule).unwrap();
+        assert!(document.starts_with("[[slot]]\nslot = 2\n"));
+        assert_eq!(
+            toml::from_str::<EcoSchedule>(&document).unwrap(),
+            schedule.clone()
+        );
+
+        let requested = EcoSchedule {
+            slots: vec![Some(slot("02:00", "04:00", &[Weekday::Tue]))],
+        };
+        assert_eq!(
+            schedule.diff(&requested),
+            vec![
+                "- slot 1: disabled",
+                "+ slot 1: 02:00-04:00 tue charge at 50% until 90% SoC",
+                "- slot 2: 02:00-04:00 tue charge at 50% until 90% SoC",
+                "+ slot 2: disabled",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_times_from_the_inverter() {
+        let mut registers = slot("02:00", "04:00", &[Weekday::Tue]).to_registers();
+        registers[1] = 0xffff;
+        assert!(matches!(
+            EcoSlot::from_registers(&registers),
+            Err(SettingsError::UnknownValue { value: 0xffff, .. })
+        ));
+
+        registers[1] = 0x043c;
+        assert!(EcoSlot::from_registers(&registers).is_err());
+
+        // Disabled slots may hold anything
+        registers[2] = 0;
+        assert_eq!(EcoSlot::from_registers(&registers).unwrap(), None);
+    }
+
+    #[test]
+    fn documents_without_numbers_fill_slots_in_order() {
+        let document = "[[slot]]\nstart = \"01:00\"\nend = \"05:00\"\ndays = [\"mon\"]\n\
+                        mode = \"charge\"\npower = 50\nsoc = 90\n";
+        let schedule: EcoSchedule = toml::from_str(document).unwrap();
+        assert_eq!(schedule.slots.len(), SLOT_COUNT);
+        assert!(schedule.slot(0).is_some());
+
+        let twice = format!("{}slot = 2\n{}slot = 2\n", document, document);
+        assert!(toml::from_str::<EcoSchedule>(&twice).is_err());
+    }
+
+    #[test]
+    fn time_of_day_rejects_invalid_times() {
+        assert!(TimeOfDay::try_from("24:00".to_owned()).is_err());
+        assert!(TimeOfDay::try_from("7".to_owned()).is_err());
+    }
+}
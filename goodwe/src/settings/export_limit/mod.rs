@@ -0,0 +1,42 @@
+use super::SettingsError;
+use crate::metrics::{connection::Connection, modbus};
+
+pub const REGISTER_EXPORT_ENABLED: u16 = 47509;
+pub const REGISTER_EXPORT_LIMIT: u16 = 47510;
+
+/// Total active power measured by the meter, positive values are exported
+pub const REGISTER_METER_ACTIVE_POWER: u16 = 36025;
+
+pub struct ExportLimit {
+    pub enabled: bool,
+    /// Maximum power in W fed into the grid while enabled
+    pub limit: u16,
+}
+
+pub fn get_export_limit(conn: &Connection) -> Result<ExportLimit, SettingsError> {
+    let registers = modbus::to_registers(&conn.read_registers(REGISTER_EXPORT_ENABLED, 2)?);
+
+    Ok(ExportLimit {
+        enabled: registers[0] != 0,
+        limit: registers[1],
+    })
+}
+
+/// Write the enable flag and the limit in one go and verify both
+pub fn set_export_limit(
+    conn: &Connection,
+    export_limit: &ExportLimit,
+) -> Result<(), SettingsError> {
+    conn.write_registers(
+        REGISTER_EXPORT_ENABLED,
+        &[export_limit.enabled as u16, export_limit.limit],
+        true,
+    )?;
+    Ok(())
+}
+
+/// Power in W currently exchanged with the grid according to the meter
+pub fn get_meter_active_power(conn: &Connection) -> Result<i32, SettingsError> {
+    let data = conn.read_registers(REGISTER_METER_ACTIVE_POWER, 2)?;
+    Ok(i32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+}
@@ -0,0 +1,88 @@
+use std::{error::Error, fmt::Display};
+
+use crate::metrics::{
+    modbus::{ExceptionCode, ModbusError},
+    MetricsError,
+};
+
+pub mod battery;
+pub mod eco_schedule;
+pub mod export_limit;
+pub mod work_mode;
+
+#[derive(Debug)]
+pub enum SettingsError {
+    /// The inverter answered the write with a Modbus exception
+    Refused(ExceptionCode),
+    /// The inverter accepted the write, but reading back shows another value
+    NotApplied {
+        expected: u16,
+        actual: u16,
+    },
+    /// The register holds a value we don't know how to interpret
+    UnknownValue {
+        setting: &'static str,
+        value: u16,
+    },
+    /// The eco mode schedule can't be programmed as requested
+    InvalidSchedule(String),
+    /// The requested value is outside of the range the inverter allows
+    OutOfRange {
+        setting: &'static str,
+        value: f32,
+        min: f32,
+        max: f32,
+    },
+    /// No battery management system reports limits, so there is no battery
+    /// to configure
+    NoBattery,
+    CommunicationError(MetricsError),
+}
+
+impl Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Refused(code) => write!(f, "Inverter refused the change: {code}"),
+            SettingsError::NotApplied { expected, actual } => write!(
+                f,
+                "Inverter did not apply the change, it reports {actual} instead of {expected}"
+            ),
+            SettingsError::UnknownValue { setting, value } => {
+                write!(f, "Unknown {setting} value {value}")
+            }
+            SettingsError::InvalidSchedule(reason) => write!(f, "Invalid schedule: {reason}"),
+            SettingsError::OutOfRange {
+                setting,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "The {setting} has to be between {min} and {max}, {value} is out of range"
+            ),
+            SettingsError::NoBattery => write!(f, "No battery management system reports limits"),
+            SettingsError::CommunicationError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for SettingsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SettingsError::CommunicationError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<MetricsError> for SettingsError {
+    fn from(e: MetricsError) -> Self {
+        match e {
+            MetricsError::ModbusError(ModbusError::Exception(code)) => SettingsError::Refused(code),
+            MetricsError::VerificationFailed {
+                expected, actual, ..
+            } => SettingsError::NotApplied { expected, actual },
+            e => SettingsError::CommunicationError(e),
+        }
+    }
+}
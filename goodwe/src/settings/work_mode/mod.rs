@@ -0,0 +1,80 @@
+use std::{fmt::Display, str::FromStr};
+
+use super::SettingsError;
+use crate::metrics::{connection::Connection, modbus};
+
+pub const REGISTER_WORK_MODE: u16 = 47000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkMode {
+    General = 0,
+    OffGrid = 1,
+    Backup = 2,
+    Eco = 3,
+}
+
+impl WorkMode {
+    pub const ALL: [WorkMode; 4] = [
+        WorkMode::General,
+        WorkMode::OffGrid,
+        WorkMode::Backup,
+        WorkMode::Eco,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WorkMode::General => "general",
+            WorkMode::OffGrid => "off-grid",
+            WorkMode::Backup => "backup",
+            WorkMode::Eco => "eco",
+        }
+    }
+}
+
+impl Display for WorkMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for WorkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        WorkMode::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<&str> = WorkMode::ALL.iter().map(|m| m.name()).collect();
+                format!(
+                    "unknown work mode '{s}', expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl TryFrom<u16> for WorkMode {
+    type Error = SettingsError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        WorkMode::ALL
+            .into_iter()
+            .find(|mode| *mode as u16 == value)
+            .ok_or(SettingsError::UnknownValue {
+                setting: "work mode",
+                value,
+            })
+    }
+}
+
+pub fn get_work_mode(conn: &Connection) -> Result<WorkMode, SettingsError> {
+    let data = conn.read_registers(REGISTER_WORK_MODE, 1)?;
+    WorkMode::try_from(modbus::to_registers(&data)[0])
+}
+
+/// Switch the work mode and verify the inverter applied it
+pub fn set_work_mode(conn: &Connection, mode: WorkMode) -> Result<(), SettingsError> {
+    conn.write_registers(REGISTER_WORK_MODE, &[mode as u16], true)?;
+    Ok(())
+}
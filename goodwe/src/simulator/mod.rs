@@ -0,0 +1,571 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::Display,
+    fs, io,
+    net::{SocketAddr, UdpSocket},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crc16::{State, MODBUS};
+use serde::Deserialize;
+
+use crate::metrics::modbus::{self, Command, ExceptionCode};
+
+const DISCOVERY_REQUEST: &[u8] = b"WIFIKIT-214028-READ";
+const ID_QUERY: [u8; 9] = [0xaa, 0x55, 0xc0, 0x7f, 0x01, 0x02, 0x00, 0x02, 0x41];
+
+/// How often the sockets check whether the simulator should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Register image and behaviour of a simulated inverter
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimulatorConfig {
+    #[serde(default = "default_serial_number")]
+    pub serial_number: String,
+    #[serde(default = "default_firmware")]
+    pub firmware: String,
+    /// Address and WiFi name reported in the discovery answer
+    #[serde(default = "default_ip_address")]
+    pub ip_address: String,
+    #[serde(default = "default_wifi_name")]
+    pub wifi_name: String,
+    /// Modbus unit address the simulator answers to
+    #[serde(default = "default_unit")]
+    pub unit: u8,
+    /// Ranges of registers that exist and read as 0 unless set below
+    #[serde(default, rename = "block")]
+    pub blocks: Vec<RegisterBlock>,
+    /// Register values, keyed by register. A list sets consecutive registers.
+    #[serde(default)]
+    pub registers: BTreeMap<String, RegisterValue>,
+    #[serde(default)]
+    pub faults: Faults,
+    /// Changes applied once the given time since the start has passed
+    #[serde(default)]
+    pub script: Vec<ScriptStep>,
+    /// Start the script over after this many seconds
+    pub script_repeat_seconds: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegisterBlock {
+    pub start: u16,
+    pub count: u16,
+}
+
+/// Negative values are stored as two's complement
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum RegisterValue {
+    Single(i32),
+    Multiple(Vec<i32>),
+}
+
+/// Fault injection, rates are probabilities between 0 and 1 per request
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Faults {
+    /// Don't answer at all
+    #[serde(default)]
+    pub drop_rate: f64,
+    /// Corrupt the checksum of the answer
+    #[serde(default)]
+    pub bad_crc_rate: f64,
+    /// Answer Modbus requests with `exception_code` instead
+    #[serde(default)]
+    pub exception_rate: f64,
+    #[serde(default = "default_exception_code")]
+    pub exception_code: u8,
+    /// Wait before answering
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptStep {
+    pub at_seconds: f64,
+    pub register: u16,
+    pub value: RegisterValue,
+}
+
+fn default_serial_number() -> String {
+    "5020KETU000W0000".to_owned()
+}
+
+fn default_firmware() -> String {
+    "04029-23-S".to_owned()
+}
+
+fn default_ip_address() -> String {
+    "127.0.0.1".to_owned()
+}
+
+fn default_wifi_name() -> String {
+    "Solar-WiFi000000".to_owned()
+}
+
+fn default_unit() -> u8 {
+    modbus::DEFAULT_ADDR
+}
+
+fn default_exception_code() -> u8 {
+    // Slave device busy
+    0x06
+}
+
+#[derive(Debug)]
+pub enum SimulatorError {
+    IoError(io::Error),
+    ParseError(toml::de::Error),
+    InvalidRegister(String),
+}
+
+impl Display for SimulatorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimulatorError::IoError(e) => write!(f, "Unable to read simulator config: {e}"),
+            SimulatorError::ParseError(e) => write!(f, "Unable to parse simulator config: {e}"),
+            SimulatorError::InvalidRegister(key) => write!(f, "Invalid register '{key}'"),
+        }
+    }
+}
+
+impl Error for SimulatorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SimulatorError::IoError(e) => Some(e),
+            SimulatorError::ParseError(e) => Some(e),
+            SimulatorError::InvalidRegister(_) => None,
+        }
+    }
+}
+
+impl SimulatorConfig {
+    pub fn load(path: &Path) -> Result<Self, SimulatorError> {
+        let content = fs::read_to_string(path).map_err(SimulatorError::IoError)?;
+        Self::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, SimulatorError> {
+        toml::from_str(content).map_err(SimulatorError::ParseError)
+    }
+
+    fn register_image(&self) -> Result<BTreeMap<u16, u16>, SimulatorError> {
+        let mut image = BTreeMap::new();
+        for block in &self.blocks {
+            for register in (block.start..=u16::MAX).take(block.count as usize) {
+                image.insert(register, 0);
+            }
+        }
+        for (key, value) in &self.registers {
+            let register = key
+                .parse()
+                .map_err(|_| SimulatorError::InvalidRegister(key.clone()))?;
+            set_value(&mut image, register, value);
+        }
+        Ok(image)
+    }
+}
+
+fn set_value(image: &mut BTreeMap<u16, u16>, register: u16, value: &RegisterValue) {
+    match value {
+        RegisterValue::Single(value) => {
+            image.insert(register, *value as u16);
+        }
+        RegisterValue::Multiple(values) => {
+            for (offset, value) in values.iter().enumerate() {
+                image.insert(register.wrapping_add(offset as u16), *value as u16);
+            }
+        }
+    }
+}
+
+struct ScriptState {
+    cycle: u64,
+    next_step: usize,
+}
+
+/// Simulated inverter, answering requests from its register image
+pub struct Simulator {
+    config: SimulatorConfig,
+    registers: Mutex<BTreeMap<u16, u16>>,
+    started: Instant,
+    script: Mutex<ScriptState>,
+    random: Mutex<u64>,
+}
+
+impl Simulator {
+    pub fn new(config: SimulatorConfig) -> Result<Self, SimulatorError> {
+        let registers = Mutex::new(config.register_image()?);
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+
+        Ok(Simulator {
+            config,
+            registers,
+            started: Instant::now(),
+            script: Mutex::new(ScriptState {
+                cycle: 0,
+                next_step: 0,
+            }),
+            // xorshift must not start at 0
+            random: Mutex::new(seed | 1),
+        })
+    }
+
+    /// Current value of a register, `None` if it does not exist
+    pub fn register(&self, register: u16) -> Option<u16> {
+        self.registers.lock().unwrap().get(&register).copied()
+    }
+
+    /// Answer a frame received on the inverter port, `None` means no answer
+    pub fn handle_request(&self, request: &[u8]) -> Option<Vec<u8>> {
+        if request == ID_QUERY {
+            return Some(self.id_response());
+        }
+
+        // Unit address, function, register, count or value and the CRC
+        if request.len() < 8
+            || State::<MODBUS>::calculate(request) != 0
+            || request[0] != self.config.unit
+        {
+            return None;
+        }
+        self.run_script();
+
+        let register = u16::from_be_bytes([request[2], request[3]]);
+        let param = u16::from_be_bytes([request[4], request[5]]);
+        let result = match request[1] {
+            f if f == Command::ReadMulti as u8 => self.read(register, param),
+            f if f == Command::WriteSingle as u8 => {
+                self.write(Command::WriteSingle, register, &[param])
+            }
+            f if f == Command::WriteMulti as u8 => {
+                let data = &request[7..request.len() - 2];
+                if data.len() != param as usize * 2 || request[6] as usize != data.len() {
+                    return None;
+                }
+                self.write(Command::WriteMulti, register, &modbus::to_registers(data))
+            }
+            _ => Err(ExceptionCode::IllegalFunction),
+        };
+
+        let body = match result {
+            Ok(body) => body,
+            Err(code) => vec![self.config.unit, request[1] | 0x80, code.into()],
+        };
+        Some(modbus_frame(&body))
+    }
+
+    /// Answer a frame received on the discovery port
+    pub fn handle_discovery(&self, request: &[u8]) -> Option<Vec<u8>> {
+        (request == DISCOVERY_REQUEST).then(|| {
+            format!(
+                "{},{},{}",
+                self.config.ip_address, self.config.serial_number, self.config.wifi_name
+            )
+            .into_bytes()
+        })
+    }
+
+    fn read(&self, start: u16, count: u16) -> Result<Vec<u8>, ExceptionCode> {
+        if count == 0 || count > modbus::MAX_READ_REGISTERS {
+            return Err(ExceptionCode::IllegalDataValue);
+        }
+
+        let registers = self.registers.lock().unwrap();
+        let mut body = vec![
+            self.config.unit,
+            Command::ReadMulti as u8,
+            (count * 2) as u8,
+        ];
+        if start as u32 + count as u32 > 1 << 16 {
+            return Err(ExceptionCode::IllegalDataAddress);
+        }
+        for register in (start..=u16::MAX).take(count as usize) {
+            let value = registers
+                .get(&register)
+                .ok_or(ExceptionCode::IllegalDataAddress)?;
+            body.extend_from_slice(&value.to_be_bytes());
+        }
+        Ok(body)
+    }
+
+    /// Only registers of the image can be written
+    fn write(
+        &self,
+        command: Command,
+        start: u16,
+        values: &[u16],
+    ) -> Result<Vec<u8>, ExceptionCode> {
+        let mut registers = self.registers.lock().unwrap();
+        if start as u32 + values.len() as u32 > 1 << 16
+            || !(start..=u16::MAX)
+                .take(values.len())
+                .all(|register| registers.contains_key(&register))
+        {
+            return Err(ExceptionCode::IllegalDataAddress);
+        }
+        for (offset, value) in values.iter().enumerate() {
+            registers.insert(start + offset as u16, *value);
+        }
+
+        let param = match command {
+            Command::WriteSingle => values[0],
+            _ => values.len() as u16,
+        };
+        let mut body = vec![self.config.unit, command as u8];
+        body.extend_from_slice(&start.to_be_bytes());
+        body.extend_from_slice(&param.to_be_bytes());
+        Ok(body)
+    }
+
+    /// The ID answer has no CRC, but a sum of all bytes as checksum
+    fn id_response(&self) -> Vec<u8> {
+        let mut frame = vec![0xaa, 0x55, 0x7f, 0xc0, 0x01, 0x82, 76];
+        let mut payload = [0_u8; 76];
+        copy_padded(&mut payload[31..47], &self.config.serial_number);
+        copy_padded(&mut payload[64..74], &self.config.firmware);
+        frame.extend_from_slice(&payload);
+
+        let checksum = frame
+            .iter()
+            .fold(0_u16, |sum, b| sum.wrapping_add(*b as u16));
+        frame.extend_from_slice(&checksum.to_be_bytes());
+        frame
+    }
+
+    /// Apply the script steps that are due
+    fn run_script(&self) {
+        if self.config.script.is_empty() {
+            return;
+        }
+
+        let mut elapsed = self.started.elapsed().as_secs_f64();
+        let mut cycle = 0;
+        if let Some(repeat) = self.config.script_repeat_seconds.filter(|r| *r > 0.0) {
+            cycle = (elapsed / repeat) as u64;
+            elapsed %= repeat;
+        }
+
+        let mut state = self.script.lock().unwrap();
+        if state.cycle != cycle {
+            state.cycle = cycle;
+            state.next_step = 0;
+        }
+
+        let mut registers = self.registers.lock().unwrap();
+        while let Some(step) = self.config.script.get(state.next_step) {
+            if step.at_seconds > elapsed {
+                break;
+            }
+            set_value(&mut registers, step.register, &step.value);
+            state.next_step += 1;
+        }
+    }
+
+    /// Decide with probability `rate`
+    fn chance(&self, rate: f64) -> bool {
+        if rate <= 0.0 {
+            return false;
+        }
+        let mut state = self.random.lock().unwrap();
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        (*state >> 11) as f64 / (1_u64 << 53) as f64 <= rate
+    }
+
+    /// Answer a request on the inverter port with the configured faults
+    fn answer_with_faults(&self, request: &[u8]) -> Option<Vec<u8>> {
+        let faults = &self.config.faults;
+        if self.chance(faults.drop_rate) {
+            return None;
+        }
+
+        let mut response = if request != ID_QUERY && self.chance(faults.exception_rate) {
+            if request.len() < 2 {
+                return None;
+            }
+            modbus_frame(&[request[0], request[1] | 0x80, faults.exception_code])
+        } else {
+            self.handle_request(request)?
+        };
+
+        if self.chance(faults.bad_crc_rate) {
+            if let Some(last) = response.last_mut() {
+                *last ^= 0xff;
+            }
+        }
+        if faults.delay_ms > 0 {
+            thread::sleep(Duration::from_millis(faults.delay_ms));
+        }
+        Some(response)
+    }
+
+    /// Listen for requests on both ports until the returned handle is dropped
+    pub fn spawn(
+        self,
+        inverter_addr: SocketAddr,
+        discovery_addr: SocketAddr,
+    ) -> io::Result<SimulatorHandle> {
+        let simulator = Arc::new(self);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let inverter = UdpSocket::bind(inverter_addr)?;
+        let discovery = UdpSocket::bind(discovery_addr)?;
+        let handle = SimulatorHandle {
+            inverter_addr: inverter.local_addr()?,
+            discovery_addr: discovery.local_addr()?,
+            stop: stop.clone(),
+        };
+
+        let inverter_simulator = simulator.clone();
+        let inverter_stop = stop.clone();
+        serve(inverter, inverter_stop, move |request| {
+            inverter_simulator.answer_with_faults(request)
+        })?;
+        serve(discovery, stop, move |request| {
+            simulator.handle_discovery(request)
+        })?;
+
+        Ok(handle)
+    }
+}
+
+/// Running simulator, stops listening when dropped
+pub struct SimulatorHandle {
+    pub inverter_addr: SocketAddr,
+    pub discovery_addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for SimulatorHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn serve(
+    sock: UdpSocket,
+    stop: Arc<AtomicBool>,
+    answer: impl Fn(&[u8]) -> Option<Vec<u8>> + Send + 'static,
+) -> io::Result<()> {
+    sock.set_read_timeout(Some(POLL_INTERVAL))?;
+    thread::spawn(move || {
+        let mut buf = [0; 1024];
+        while !stop.load(Ordering::Relaxed) {
+            let Ok((size, from)) = sock.recv_from(&mut buf) else {
+                continue;
+            };
+            if let Some(response) = answer(&buf[0..size]) {
+                let _ = sock.send_to(&response, from);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn modbus_frame(body: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0xaa, 0x55];
+    frame.extend_from_slice(body);
+    frame.append(&mut modbus::crc(body));
+    frame
+}
+
+fn copy_padded(target: &mut [u8], value: &str) {
+    let bytes = value.as_bytes();
+    let len = bytes.len().min(target.len());
+    target[0..len].copy_from_slice(&bytes[0..len]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        discovery, identify,
+        metrics::modbus::{ModbusError, DEFAULT_ADDR},
+    };
+
+    fn simulator(config: &str) -> Simulator {
+        Simulator::new(SimulatorConfig::parse(config).unwrap_or_else(|e| panic!("{e}")))
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    const CONFIG: &str = r#"
+        serial_number = "5020KETU000W0001"
+
+        [[block]]
+        start = 100
+        count = 4
+
+        [registers]
+        101 = [-1, 7]
+    "#;
+
+    #[test]
+    fn answers_identification_and_discovery() {
+        let simulator = simulator(CONFIG);
+
+        let id = simulator.handle_request(&ID_QUERY).unwrap();
+        let id = identify::decode_response(&id).unwrap_or_else(|e| panic!("{e}"));
+        assert_eq!(id.serial_number, "5020KETU000W0001");
+
+        let response = simulator.handle_discovery(DISCOVERY_REQUEST).unwrap();
+        let response = discovery::decode_response(&response).unwrap();
+        assert_eq!(response.serial_number, "5020KETU000W0001");
+    }
+
+    #[test]
+    fn reads_register_image() {
+        let simulator = simulator(CONFIG);
+        let request = modbus::create_command(Command::ReadMulti, DEFAULT_ADDR, 100, 3);
+        let response = simulator.handle_request(&request).unwrap();
+        assert_eq!(
+            modbus::get_payload(&response, DEFAULT_ADDR, 3).unwrap(),
+            vec![0x00, 0x00, 0xff, 0xff, 0x00, 0x07]
+        );
+    }
+
+    #[test]
+    fn refuses_unknown_registers() {
+        let simulator = simulator(CONFIG);
+        let request = modbus::create_command(Command::ReadMulti, DEFAULT_ADDR, 102, 3);
+        let response = simulator.handle_request(&request).unwrap();
+        assert!(matches!(
+            modbus::get_payload(&response, DEFAULT_ADDR, 3),
+            Err(ModbusError::Exception(ExceptionCode::IllegalDataAddress))
+        ));
+    }
+
+    #[test]
+    fn accepts_writes() {
+        let simulator = simulator(CONFIG);
+        let request = modbus::create_write_multi_command(DEFAULT_ADDR, 102, &[3, 4]);
+        let response = simulator.handle_request(&request).unwrap();
+        assert!(
+            modbus::check_write_response(&response, DEFAULT_ADDR, Command::WriteMulti, 102, 2)
+                .is_ok()
+        );
+        assert_eq!(simulator.register(103), Some(4));
+    }
+
+    #[test]
+    fn ignores_corrupt_requests() {
+        let simulator = simulator(CONFIG);
+        let mut request = modbus::create_command(Command::ReadMulti, DEFAULT_ADDR, 100, 1);
+        request[7] ^= 0xff;
+        assert!(simulator.handle_request(&request).is_none());
+    }
+}
@@ -0,0 +1,429 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Sends frames to an inverter and receives its answers
+pub trait Transport: Send + Sync {
+    fn send(&self, frame: &[u8]) -> io::Result<()>;
+    /// Wait up to `timeout` for the next frame and return it with its sender
+    fn receive(&self, timeout: Duration) -> io::Result<(Vec<u8>, SocketAddr)>;
+    /// Drop frames that already arrived, but were not received yet
+    fn discard_pending(&self) -> io::Result<()>;
+}
+
+/// Where frames go
+#[derive(Clone, Default)]
+enum Mode {
+    #[default]
+    Network,
+    Record(Arc<Recorder>),
+    Replay(Arc<Recording>),
+}
+
+/// How transports are opened: talking to the network, optionally recording
+/// every frame, or answering requests from a recording. Cloned options share
+/// the recording.
+#[derive(Clone, Default)]
+pub struct TransportOptions {
+    mode: Mode,
+}
+
+impl TransportOptions {
+    /// Record every frame sent and received to `path`
+    pub fn record_to(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(Self {
+            mode: Mode::Record(Arc::new(Recorder {
+                file: Mutex::new(file),
+            })),
+        })
+    }
+
+    /// Answer all requests from the recording in `path` instead of the
+    /// network
+    pub fn replay_from(path: &Path) -> Result<Self, RecordingError> {
+        Ok(Self {
+            mode: Mode::Replay(Arc::new(Recording::load(path)?)),
+        })
+    }
+
+    /// Open a transport to the single host `peer`, e.g. "192.168.1.50:8899"
+    pub fn connect(&self, peer: &str) -> io::Result<Box<dyn Transport>> {
+        self.open(peer, false)
+    }
+
+    /// Open a transport that broadcasts its requests to `peer` and receives
+    /// answers from any host
+    pub fn broadcast(&self, peer: &str) -> io::Result<Box<dyn Transport>> {
+        self.open(peer, true)
+    }
+
+    fn open(&self, peer: &str, broadcast: bool) -> io::Result<Box<dyn Transport>> {
+        let recorder = match &self.mode {
+            Mode::Network => None,
+            Mode::Record(recorder) => Some(recorder.clone()),
+            Mode::Replay(recording) => {
+                return Ok(Box::new(ReplayTransport {
+                    recording: recording.clone(),
+                    pending: Mutex::new(VecDeque::new()),
+                }))
+            }
+        };
+
+        let peer = peer
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "target has no address"))?;
+        // The socket has to be of the same address family as the peer
+        let local: IpAddr = match peer {
+            SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        let sock = UdpSocket::bind((local, 0))?;
+        if broadcast {
+            sock.set_broadcast(true)?;
+        } else {
+            // Only accept datagrams from the inverter
+            sock.connect(peer)?;
+        }
+
+        Ok(Box::new(UdpTransport {
+            sock,
+            peer,
+            connected: !broadcast,
+            recorder,
+        }))
+    }
+}
+
+/// Port the inverter protocol uses unless the target names another one
+pub const INVERTER_PORT: u16 = 8899;
+
+/// Address of the inverter protocol of `target`, which is a host name or IP
+/// address, optionally followed by a port (e.g. `192.168.1.50:18899` or
+/// `[::1]:18899`)
+pub fn inverter_address(target: &str) -> String {
+    if target.parse::<SocketAddr>().is_ok() {
+        return target.to_owned();
+    }
+    match target.parse::<IpAddr>() {
+        Ok(ip) => SocketAddr::new(ip, INVERTER_PORT).to_string(),
+        Err(_) if target.contains(':') => target.to_owned(),
+        Err(_) => format!("{target}:{INVERTER_PORT}"),
+    }
+}
+
+struct UdpTransport {
+    sock: UdpSocket,
+    peer: SocketAddr,
+    /// Connected sockets have to use `send`, BSD and macOS refuse `send_to`
+    /// on them
+    connected: bool,
+    recorder: Option<Arc<Recorder>>,
+}
+
+impl Transport for UdpTransport {
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+        if self.connected {
+            self.sock.send(frame)?;
+        } else {
+            self.sock.send_to(frame, self.peer)?;
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.record(Direction::Request, self.peer, frame)?;
+        }
+        Ok(())
+    }
+
+    fn receive(&self, timeout: Duration) -> io::Result<(Vec<u8>, SocketAddr)> {
+        if timeout.is_zero() {
+            return Err(io::Error::from(ErrorKind::TimedOut));
+        }
+        self.sock.set_read_timeout(Some(timeout))?;
+
+        let mut buf = [0; 1024];
+        let (size, from) = self.sock.recv_from(&mut buf)?;
+        if let Some(recorder) = &self.recorder {
+            recorder.record(Direction::Response, from, &buf[0..size])?;
+        }
+        Ok((buf[0..size].to_vec(), from))
+    }
+
+    fn discard_pending(&self) -> io::Result<()> {
+        self.sock.set_nonblocking(true)?;
+        let mut buf = [0; 1024];
+        while self.sock.recv(&mut buf).is_ok() {}
+        self.sock.set_nonblocking(false)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Direction {
+    Request,
+    Response,
+}
+
+/// One line of a recording
+#[derive(Serialize, Deserialize)]
+struct RecordedFrame {
+    timestamp: String,
+    direction: Direction,
+    /// Receiver of a request, sender of a response
+    peer: SocketAddr,
+    /// Frame as hex string
+    frame: String,
+}
+
+struct Recorder {
+    file: Mutex<File>,
+}
+
+impl Recorder {
+    fn record(&self, direction: Direction, peer: SocketAddr, frame: &[u8]) -> io::Result<()> {
+        let record = RecordedFrame {
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            direction,
+            peer,
+            frame: frame.iter().map(|b| format!("{b:02x}")).collect(),
+        };
+        let mut line = serde_json::to_string(&record).map_err(io::Error::other)?;
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    IoError(io::Error),
+    FormatError { line: usize, reason: String },
+}
+
+impl Display for RecordingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordingError::IoError(e) => write!(f, "Unable to read recording: {e}"),
+            RecordingError::FormatError { line, reason } => {
+                write!(f, "Invalid recording in line {line}: {reason}")
+            }
+        }
+    }
+}
+
+impl Error for RecordingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RecordingError::IoError(e) => Some(e),
+            RecordingError::FormatError { .. } => None,
+        }
+    }
+}
+
+type Responses = Vec<(Vec<u8>, SocketAddr)>;
+
+/// The answers recorded for one request. A request sent several times gets
+/// the recorded answers in turn, starting over after the last one.
+#[derive(Default)]
+struct Exchange {
+    answers: Vec<Responses>,
+    next: usize,
+}
+
+struct Recording {
+    exchanges: Mutex<HashMap<Vec<u8>, Exchange>>,
+}
+
+impl Recording {
+    fn load(path: &Path) -> Result<Self, RecordingError> {
+        let content = fs::read_to_string(path).map_err(RecordingError::IoError)?;
+
+        let mut exchanges: HashMap<Vec<u8>, Exchange> = HashMap::new();
+        let mut last_request = None;
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let format_error = |reason: String| RecordingError::FormatError {
+                line: index + 1,
+                reason,
+            };
+            let record: RecordedFrame =
+                serde_json::from_str(line).map_err(|e| format_error(e.to_string()))?;
+            let frame = decode_hex(&record.frame)
+                .ok_or_else(|| format_error(format!("'{}' is not a hex string", record.frame)))?;
+
+            match record.direction {
+                Direction::Request => {
+                    exchanges
+                        .entry(frame.clone())
+                        .or_default()
+                        .answers
+                        .push(Vec::new());
+                    last_request = Some(frame);
+                }
+                // Responses without a request were sent before the
+                // recording started and can't be matched
+                Direction::Response => {
+                    if let Some(request) = &last_request {
+                        if let Some(answers) = exchanges
+                            .get_mut(request)
+                            .and_then(|exchange| exchange.answers.last_mut())
+                        {
+                            answers.push((frame, record.peer));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Recording {
+            exchanges: Mutex::new(exchanges),
+        })
+    }
+
+    fn answer(&self, request: &[u8]) -> Responses {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        match exchanges.get_mut(request) {
+            Some(exchange) => {
+                let responses = exchange.answers[exchange.next].clone();
+                exchange.next = (exchange.next + 1) % exchange.answers.len();
+                responses
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Answers requests from a recording. Requests that were not recorded time
+/// out immediately.
+struct ReplayTransport {
+    recording: Arc<Recording>,
+    pending: Mutex<VecDeque<(Vec<u8>, SocketAddr)>>,
+}
+
+impl Transport for ReplayTransport {
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+        self.pending
+            .lock()
+            .unwrap()
+            .extend(self.recording.answer(frame));
+        Ok(())
+    }
+
+    fn receive(&self, _timeout: Duration) -> io::Result<(Vec<u8>, SocketAddr)> {
+        self.pending
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| io::Error::from(ErrorKind::TimedOut))
+    }
+
+    fn discard_pending(&self) -> io::Result<()> {
+        self.pending.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(direction: &str, frame: &str) -> String {
+        format!(
+            r#"{{"timestamp":"2024-01-01T00:00:00.000Z","direction":"{direction}","peer":"192.168.1.50:8899","frame":"{frame}"}}"#
+        )
+    }
+
+    fn load(lines: &[String]) -> Result<Recording, RecordingError> {
+        let path = std::env::temp_dir().join(format!(
+            "goodwe-prom-recording-{}-{}.jsonl",
+            std::process::id(),
+            lines.len()
+        ));
+        fs::write(&path, lines.join("\n")).unwrap();
+        let recording = Recording::load(&path);
+        fs::remove_file(&path).unwrap();
+        recording
+    }
+
+    #[test]
+    fn replay_cycles_through_recorded_answers() {
+        let recording = load(&[
+            line("response", "ff"),
+            line("request", "0102"),
+            line("response", "aa01"),
+            line("request", "0102"),
+            line("response", "aa02"),
+            line("response", "aa03"),
+        ])
+        .unwrap_or_else(|e| panic!("{e}"));
+
+        let frames = |responses: Responses| -> Vec<Vec<u8>> {
+            responses.into_iter().map(|(frame, _)| frame).collect()
+        };
+        assert_eq!(frames(recording.answer(&[1, 2])), vec![vec![0xaa, 0x01]]);
+        assert_eq!(
+            frames(recording.answer(&[1, 2])),
+            vec![vec![0xaa, 0x02], vec![0xaa, 0x03]]
+        );
+        assert_eq!(frames(recording.answer(&[1, 2])), vec![vec![0xaa, 0x01]]);
+        assert!(recording.answer(&[0xff]).is_empty());
+    }
+
+    fn exchange_frames(inverter: UdpSocket) {
+        let peer = inverter.local_addr().unwrap().to_string();
+        let transport = TransportOptions::default().connect(&peer).unwrap();
+
+        transport.send(&[1, 2]).unwrap();
+        let mut buf = [0; 16];
+        let (size, from) = inverter.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..size], [1, 2]);
+
+        inverter.send_to(&[3, 4], from).unwrap();
+        let (frame, _) = transport.receive(Duration::from_secs(1)).unwrap();
+        assert_eq!(frame, [3, 4]);
+    }
+
+    #[test]
+    fn connected_transport_exchanges_frames() {
+        exchange_frames(UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap());
+    }
+
+    #[test]
+    fn connected_transport_exchanges_frames_over_ipv6() {
+        // Hosts without IPv6 can't run this test
+        if let Ok(inverter) = UdpSocket::bind((Ipv6Addr::LOCALHOST, 0)) {
+            exchange_frames(inverter);
+        }
+    }
+
+    #[test]
+    fn load_rejects_invalid_frames() {
+        assert!(matches!(
+            load(&[line("request", "0102"), line("response", "a")]),
+            Err(RecordingError::FormatError { line: 2, .. })
+        ));
+    }
+}
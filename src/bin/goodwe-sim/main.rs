@@ -0,0 +1,66 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    process::ExitCode,
+    thread,
+};
+
+use clap::Parser;
+use goodwe::simulator::{Simulator, SimulatorConfig};
+
+/// Register image of a GW20K-ET used without a configuration file
+const DEFAULT_CONFIG: &str = include_str!("gw20k-et.toml");
+
+/// Simulated GoodWe inverter answering discovery, identification and Modbus
+/// requests over UDP
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    /// Register image, faults and script of the simulated inverter
+    #[clap(long, env)]
+    config: Option<PathBuf>,
+    /// Address to listen on
+    #[clap(long, env, default_value_t = IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
+    listen: IpAddr,
+    /// Port of the inverter protocol
+    #[clap(long, env, default_value_t = 8899)]
+    port: u16,
+    /// Port the discovery trigger is sent to
+    #[clap(long, env, default_value_t = 48899)]
+    discovery_port: u16,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let config = match &cli.config {
+        Some(path) => SimulatorConfig::load(path),
+        None => SimulatorConfig::parse(DEFAULT_CONFIG),
+    };
+    let simulator = match config.and_then(Simulator::new) {
+        Ok(simulator) => simulator,
+        Err(e) => {
+            println!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let handle = match simulator.spawn(
+        SocketAddr::new(cli.listen, cli.port),
+        SocketAddr::new(cli.listen, cli.discovery_port),
+    ) {
+        Ok(handle) => handle,
+        Err(e) => {
+            println!("Unable to listen: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!(
+        "Simulating an inverter on {}, discovery on {}",
+        handle.inverter_addr, handle.discovery_addr
+    );
+    loop {
+        thread::park();
+    }
+}
@@ -0,0 +1,84 @@
+use std::process::ExitCode;
+
+use clap::{Args, Subcommand};
+use goodwe::{
+    safety::WriteOptions,
+    settings::battery::{self, BatterySetting},
+    transport::TransportOptions,
+};
+
+use super::{report_change, run_settings_command};
+
+#[derive(Subcommand)]
+pub enum BatteryCommands {
+    /// Print the battery settings and the limits of the BMS
+    Get,
+    /// Change one or more battery settings
+    Set(BatterySetArgs),
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = true)]
+pub struct BatterySetArgs {
+    /// Depth of discharge in % while connected to the grid
+    #[clap(long)]
+    dod_on_grid: Option<u16>,
+    /// Depth of discharge in % while off grid
+    #[clap(long)]
+    dod_off_grid: Option<u16>,
+    /// Maximum charge current in A
+    #[clap(long)]
+    max_charge_current: Option<f32>,
+    /// Maximum discharge current in A
+    #[clap(long)]
+    max_discharge_current: Option<f32>,
+    /// State of charge in % kept in reserve for backup operation
+    #[clap(long)]
+    soc_reserve: Option<u16>,
+}
+
+impl BatterySetArgs {
+    fn settings(&self) -> Vec<BatterySetting> {
+        [
+            self.dod_on_grid.map(BatterySetting::DepthOfDischargeOnGrid),
+            self.dod_off_grid
+                .map(BatterySetting::DepthOfDischargeOffGrid),
+            self.max_charge_current
+                .map(BatterySetting::MaxChargeCurrent),
+            self.max_discharge_current
+                .map(BatterySetting::MaxDischargeCurrent),
+            self.soc_reserve.map(BatterySetting::SocReserve),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+pub fn run(
+    target: &str,
+    transport: &TransportOptions,
+    write_options: &WriteOptions,
+    command: &BatteryCommands,
+) -> ExitCode {
+    run_settings_command(target, transport, write_options, |conn| match command {
+        BatteryCommands::Get => {
+            let settings = battery::get_battery_settings(conn)?;
+            let limits = battery::get_bms_limits(conn)?;
+            println!("Battery Settings");
+            println!("{settings}");
+            println!("BMS Limits");
+            println!(" - Charge Current: {} A", limits.charge_current);
+            println!(" - Discharge Current: {} A", limits.discharge_current);
+            Ok(())
+        }
+        BatteryCommands::Set(args) => {
+            let settings = args.settings();
+            battery::set_battery_settings(conn, &settings)?;
+            for setting in settings {
+                report_change(write_options, &format!("Changed the {}", setting.name()));
+            }
+            Ok(())
+        }
+    })
+}
@@ -0,0 +1,65 @@
+use std::process::ExitCode;
+
+use clap::Subcommand;
+use goodwe::{
+    safety::WriteOptions,
+    settings::export_limit::{self, ExportLimit},
+    transport::TransportOptions,
+};
+
+use super::{report_change, run_settings_command};
+
+#[derive(Subcommand)]
+pub enum ExportLimitCommands {
+    /// Print the export limit and the power currently exported
+    Get,
+    /// Enable the export limit and set it to the given power in W
+    Set { limit: u16 },
+    /// Disable the export limit
+    Disable,
+}
+
+pub fn run(
+    target: &str,
+    transport: &TransportOptions,
+    write_options: &WriteOptions,
+    command: &ExportLimitCommands,
+) -> ExitCode {
+    run_settings_command(target, transport, write_options, |conn| match command {
+        ExportLimitCommands::Get => {
+            let export_limit = export_limit::get_export_limit(conn)?;
+            let power = export_limit::get_meter_active_power(conn)?;
+            println!("Grid Export Limit");
+            println!(
+                " - Enabled: {}",
+                if export_limit.enabled { "yes" } else { "no" }
+            );
+            println!(" - Limit: {} W", export_limit.limit);
+            println!(" - Meter Active Power: {power} W (positive values are exported)");
+            Ok(())
+        }
+        ExportLimitCommands::Set { limit } => {
+            export_limit::set_export_limit(
+                conn,
+                &ExportLimit {
+                    enabled: true,
+                    limit: *limit,
+                },
+            )?;
+            report_change(write_options, &format!("Export limit set to {limit} W"));
+            Ok(())
+        }
+        ExportLimitCommands::Disable => {
+            let current = export_limit::get_export_limit(conn)?;
+            export_limit::set_export_limit(
+                conn,
+                &ExportLimit {
+                    enabled: false,
+                    limit: current.limit,
+                },
+            )?;
+            report_change(write_options, "Export limit disabled");
+            Ok(())
+        }
+    })
+}
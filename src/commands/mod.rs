@@ -0,0 +1,80 @@
+use std::{
+    io::{self, Write},
+    process::ExitCode,
+};
+
+use goodwe::{
+    metrics::connection::Connection,
+    safety::{PlannedWrite, RegisterChange, WriteOptions},
+    settings::SettingsError,
+    transport::TransportOptions,
+};
+
+pub mod battery;
+pub mod export_limit;
+pub mod mode;
+pub mod read;
+pub mod scan;
+pub mod schedule;
+
+/// Open a connection to the inverter and run a settings command on it,
+/// turning errors into a failing exit code. In a dry run, the writes the
+/// command would have sent are printed.
+fn run_settings_command(
+    target: &str,
+    transport: &TransportOptions,
+    write_options: &WriteOptions,
+    command: impl FnOnce(&Connection) -> Result<(), SettingsError>,
+) -> ExitCode {
+    let result = Connection::open_with(target, transport)
+        .map(|conn| conn.with_write_options(write_options.clone()))
+        .map_err(SettingsError::from)
+        .and_then(|conn| command(&conn).map(|_| conn));
+
+    match result {
+        Ok(conn) if write_options.dry_run => {
+            for write in conn.take_planned_writes() {
+                print_planned_write(&write);
+            }
+            println!("Dry run, nothing was changed");
+            ExitCode::SUCCESS
+        }
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            println!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Tell the user about a change the command made. A dry run made none, the
+/// planned writes are printed instead.
+fn report_change(write_options: &WriteOptions, message: &str) {
+    if !write_options.dry_run {
+        println!("{message}");
+    }
+}
+
+fn print_planned_write(write: &PlannedWrite) {
+    for change in &write.changes {
+        println!("Would write register {change}");
+    }
+    let frame: Vec<String> = write.frame.iter().map(|b| format!("{b:02x}")).collect();
+    println!("Would send frame: {}", frame.join(" "));
+}
+
+/// Show the pending changes and ask on the terminal whether to apply them
+pub fn confirm_changes(changes: &[RegisterChange]) -> bool {
+    println!("About to change:");
+    for change in changes {
+        println!(" - Register {change}");
+    }
+    print!("Apply? [y/N] ");
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim(), "y" | "Y" | "yes"),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,37 @@
+use std::process::ExitCode;
+
+use clap::Subcommand;
+use goodwe::{
+    safety::WriteOptions,
+    settings::work_mode::{self, WorkMode},
+    transport::TransportOptions,
+};
+
+use super::{report_change, run_settings_command};
+
+#[derive(Subcommand)]
+pub enum ModeCommands {
+    /// Print the current work mode
+    Get,
+    /// Switch to another work mode (general, off-grid, backup or eco)
+    Set { mode: WorkMode },
+}
+
+pub fn run(
+    target: &str,
+    transport: &TransportOptions,
+    write_options: &WriteOptions,
+    command: &ModeCommands,
+) -> ExitCode {
+    run_settings_command(target, transport, write_options, |conn| match command {
+        ModeCommands::Get => {
+            println!("Work mode: {}", work_mode::get_work_mode(conn)?);
+            Ok(())
+        }
+        ModeCommands::Set { mode } => {
+            work_mode::set_work_mode(conn, *mode)?;
+            report_change(write_options, &format!("Work mode set to {mode}"));
+            Ok(())
+        }
+    })
+}
@@ -0,0 +1,125 @@
+use std::process::ExitCode;
+
+use clap::Args;
+use goodwe::{
+    metrics::{
+        connection::Connection,
+        modbus::{self, MAX_READ_REGISTERS},
+        MetricsError,
+    },
+    transport::TransportOptions,
+};
+
+#[derive(Args)]
+pub struct ReadArgs {
+    /// First register to read
+    #[clap(long)]
+    start: u16,
+    /// Number of registers to read
+    #[clap(long, default_value_t = 1)]
+    count: u16,
+    /// Modbus unit address, decimal or hex (e.g. 0xf7)
+    #[clap(long, default_value = "0xf7", value_parser = parse_unit)]
+    unit: u8,
+}
+
+pub fn parse_unit(value: &str) -> Result<u8, String> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|e| format!("invalid unit address '{value}': {e}"))
+}
+
+pub fn run(target: &str, transport: &TransportOptions, args: &ReadArgs) -> ExitCode {
+    // The last register read is 65535 at most
+    if args.start as u32 + args.count as u32 > 1 << 16 {
+        println!("Error: Registers to read exceed the address range");
+        return ExitCode::FAILURE;
+    }
+
+    match read(target, transport, args) {
+        Ok(registers) => {
+            print_registers(args.start, &registers);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            println!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Read the registers in as many requests as needed
+fn read(
+    target: &str,
+    transport: &TransportOptions,
+    args: &ReadArgs,
+) -> Result<Vec<u16>, MetricsError> {
+    let conn = Connection::open_with(target, transport)?.with_unit(args.unit);
+
+    let mut registers = Vec::with_capacity(args.count as usize);
+    let mut start = args.start as u32;
+    let end = args.start as u32 + args.count as u32;
+    while start < end {
+        let count = (end - start).min(MAX_READ_REGISTERS as u32) as u16;
+        registers.extend(modbus::to_registers(
+            &conn.read_registers(start as u16, count)?,
+        ));
+        start += count as u32;
+    }
+    Ok(registers)
+}
+
+/// Print one row per register, every register is also shown combined with
+/// the next one as the high word of a 32 bit value
+fn print_registers(start: u16, registers: &[u16]) {
+    println!(
+        "{:>8} {:>6} {:>6} {:>6} {:>5} {:>11} {:>11} {:>12}",
+        "Register", "Hex", "u16", "i16", "ASCII", "u32", "i32", "f32"
+    );
+
+    for (offset, value) in registers.iter().enumerate() {
+        let ascii: String = value
+            .to_be_bytes()
+            .iter()
+            .map(|b| {
+                if b.is_ascii_graphic() || *b == b' ' {
+                    *b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        print!(
+            "{:>8} {:>#06x} {:>6} {:>6} {:>5}",
+            start as usize + offset,
+            value,
+            value,
+            *value as i16,
+            ascii
+        );
+
+        match registers.get(offset + 1) {
+            Some(next) => {
+                let pair = (*value as u32) << 16 | *next as u32;
+                println!(
+                    " {:>11} {:>11} {:>12}",
+                    pair,
+                    pair as i32,
+                    format_float(f32::from_bits(pair))
+                );
+            }
+            None => println!(),
+        }
+    }
+}
+
+fn format_float(value: f32) -> String {
+    let magnitude = value.abs();
+    if value == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        format!("{value:.3}")
+    } else {
+        format!("{value:.3e}")
+    }
+}
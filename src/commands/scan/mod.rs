@@ -0,0 +1,143 @@
+use std::{
+    io::{self, IsTerminal},
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::Args;
+use goodwe::{
+    metrics::connection::Connection,
+    scan::{self, RegisterState, ScanDump, ScanOptions},
+    transport::TransportOptions,
+};
+
+use super::read::parse_unit;
+
+#[derive(Args)]
+pub struct ScanArgs {
+    /// First register to scan
+    #[clap(long)]
+    start: u16,
+    /// Number of registers to scan
+    #[clap(long)]
+    count: u16,
+    /// Registers read with a single request
+    #[clap(long, default_value_t = 16)]
+    chunk_size: u16,
+    /// Pause between two requests in ms
+    #[clap(long, default_value_t = 100)]
+    delay_ms: u64,
+    /// Modbus unit address, decimal or hex (e.g. 0xf7)
+    #[clap(long, default_value = "0xf7", value_parser = parse_unit)]
+    unit: u8,
+    /// File to save the dump to, named after target and time by default
+    #[clap(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    before: PathBuf,
+    after: PathBuf,
+}
+
+pub fn run_scan(target: &str, transport: &TransportOptions, args: &ScanArgs) -> ExitCode {
+    // The last register scanned is 65535 at most
+    if args.start as u32 + args.count as u32 > 1 << 16 {
+        println!("Error: Registers to scan exceed the address range");
+        return ExitCode::FAILURE;
+    }
+
+    let conn = match Connection::open_with(target, transport) {
+        Ok(conn) => conn.with_unit(args.unit),
+        Err(e) => {
+            println!("Error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let options = ScanOptions {
+        chunk_size: args.chunk_size,
+        delay: Duration::from_millis(args.delay_ms),
+    };
+
+    let dump = scan::scan(&conn, args.start, args.count, &options, |start, count| {
+        println!(
+            "Scanning registers {start} to {}",
+            start as u32 + count as u32 - 1
+        );
+    });
+
+    let responding = dump
+        .registers
+        .values()
+        .filter(|state| matches!(state, RegisterState::Value(_)))
+        .count();
+    let illegal = dump
+        .registers
+        .values()
+        .filter(|state| matches!(state, RegisterState::IllegalAddress))
+        .count();
+    println!(
+        "{responding} registers responded, {illegal} are illegal addresses, {} failed",
+        dump.registers.len() - responding - illegal
+    );
+
+    let output = args.output.clone().unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "scan-{}-{}.json",
+            target,
+            dump.timestamp.replace(':', "")
+        ))
+    });
+    match dump.save(&output) {
+        Ok(_) => {
+            println!("Saved dump to {}", output.display());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            println!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn run_diff(args: &DiffArgs) -> ExitCode {
+    let (before, after) = match (ScanDump::load(&args.before), ScanDump::load(&args.after)) {
+        (Ok(before), Ok(after)) => (before, after),
+        (Err(e), _) | (_, Err(e)) => {
+            println!("Error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!("Before: {} at {}", before.target, before.timestamp);
+    println!("After:  {} at {}", after.target, after.timestamp);
+
+    let diff = before.diff(&after);
+    if diff.is_empty() {
+        println!("No registers changed");
+        return ExitCode::SUCCESS;
+    }
+
+    // Only highlight on a terminal, so the output can be piped to a file
+    let (highlight, reset) = if io::stdout().is_terminal() {
+        ("\x1b[1;33m", "\x1b[0m")
+    } else {
+        ("", "")
+    };
+    let describe = |state: &Option<RegisterState>| match state {
+        Some(state) => state.to_string(),
+        None => "not scanned".to_owned(),
+    };
+    for change in &diff {
+        println!(
+            "{highlight}{:>8}{reset}: {} -> {highlight}{}{reset}",
+            change.register,
+            describe(&change.before),
+            describe(&change.after)
+        );
+    }
+    println!("{} registers changed", diff.len());
+    ExitCode::SUCCESS
+}
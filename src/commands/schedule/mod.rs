@@ -0,0 +1,82 @@
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use clap::Subcommand;
+use goodwe::{
+    safety::WriteOptions,
+    settings::eco_schedule::{self, EcoSchedule},
+    transport::TransportOptions,
+};
+
+use super::{report_change, run_settings_command};
+
+#[derive(Subcommand)]
+pub enum ScheduleCommands {
+    /// Print the eco mode slots as a TOML document
+    Get,
+    /// Program the eco mode slots from a TOML document, showing the changes
+    /// before applying them
+    Set { file: PathBuf },
+}
+
+pub fn run(
+    target: &str,
+    transport: &TransportOptions,
+    write_options: &WriteOptions,
+    command: &ScheduleCommands,
+) -> ExitCode {
+    match command {
+        ScheduleCommands::Get => {
+            let mut schedule = None;
+            let exit_code = run_settings_command(target, transport, write_options, |conn| {
+                schedule = Some(eco_schedule::get_eco_schedule(conn)?);
+                Ok(())
+            });
+            let Some(schedule) = schedule else {
+                return exit_code;
+            };
+
+            match toml::to_string(&schedule) {
+                Ok(document) => {
+                    print!("{document}");
+                    exit_code
+                }
+                Err(e) => {
+                    println!("Error: Unable to format schedule: {e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        ScheduleCommands::Set { file } => {
+            let schedule = match load_schedule(file) {
+                Ok(schedule) => schedule,
+                Err(e) => {
+                    println!("Error: {e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            run_settings_command(target, transport, write_options, |conn| {
+                schedule.validate()?;
+                let current = eco_schedule::get_eco_schedule(conn)?;
+                let diff = current.diff(&schedule);
+                if diff.is_empty() {
+                    println!("Schedule is already up to date");
+                    return Ok(());
+                }
+
+                for line in diff {
+                    println!("{line}");
+                }
+                eco_schedule::set_eco_schedule(conn, &schedule)?;
+                report_change(write_options, "Schedule applied");
+                Ok(())
+            })
+        }
+    }
+}
+
+fn load_schedule(file: &PathBuf) -> Result<EcoSchedule, String> {
+    let content =
+        fs::read_to_string(file).map_err(|e| format!("Unable to read {}: {e}", file.display()))?;
+    toml::from_str(&content).map_err(|e| format!("Unable to parse {}: {e}", file.display()))
+}
@@ -1,14 +1,16 @@
-#![feature(iter_next_chunk)]
-#![feature(iter_advance_by)]
+use std::{fs, net::SocketAddr, path::PathBuf, process::ExitCode, str, sync::Arc, time::Duration};
 
-use std::{process::ExitCode, str};
+use clap::{Args, Parser, Subcommand};
+use goodwe::{
+    discovery, identify,
+    metrics::{self, derived::DerivedMetrics, flow::EnergyFlow, Exposition, MetricSet},
+    safety::{AuditLog, Confirmation, WriteOptions},
+    transport::TransportOptions,
+    GoodWeClient,
+};
 
-use axum::http::StatusCode;
-use clap::{Parser, Subcommand};
-
-mod discovery;
-mod identify;
-mod metrics;
+mod commands;
+mod server;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -19,106 +21,319 @@ struct Cli {
     /// The IP address of the inverter to talk to
     #[clap(long, env)]
     target: Option<String>,
+    /// Print the frames that would be sent instead of changing any settings
+    #[clap(long)]
+    dry_run: bool,
+    /// Change settings without asking for confirmation
+    #[clap(long, short)]
+    yes: bool,
+    /// Append every frame sent to and received from the inverter to a file
+    #[clap(long, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Answer requests from a recording instead of talking to the inverter
+    #[clap(long)]
+    replay: Option<PathBuf>,
+    /// File every register write is appended to
+    #[clap(long, env, default_value = "goodwe-prom-audit.log")]
+    audit_log: PathBuf,
+    /// TOML file defining additional metrics computed from the ones read
+    /// from the inverter
+    #[clap(long, env)]
+    derived_metrics: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Discover GoodWe inverters
-    Discover,
+    Discover {
+        /// Address the discovery trigger is sent to
+        #[clap(long, default_value = discovery::BROADCAST_ADDRESS)]
+        address: String,
+    },
     /// Identify the inverter and print the serial number and firmware version
     Identify,
     /// Metrics
     Metrics,
     /// Serve a metrics page that Prometheus can scrape
-    Prometheus,
+    Prometheus(PrometheusArgs),
+    /// Read or change the work mode of the inverter
+    Mode {
+        #[command(subcommand)]
+        command: commands::mode::ModeCommands,
+    },
+    /// Read or change the battery settings
+    Battery {
+        #[command(subcommand)]
+        command: commands::battery::BatteryCommands,
+    },
+    /// Read or change the limit of the power exported to the grid
+    ExportLimit {
+        #[command(subcommand)]
+        command: commands::export_limit::ExportLimitCommands,
+    },
+    /// Print raw register values, e.g. to find out what they contain
+    Read(commands::read::ReadArgs),
+    /// Read a range of registers and save which of them respond to a file
+    Scan(commands::scan::ScanArgs),
+    /// Show the registers that differ between two scans
+    Diff(commands::scan::DiffArgs),
+    /// Read or program the eco mode charge and discharge slots
+    Schedule {
+        #[command(subcommand)]
+        command: commands::schedule::ScheduleCommands,
+    },
+}
+
+#[derive(Args)]
+struct PrometheusArgs {
+    /// Address to listen on, may be given multiple times (e.g. `[::]:8080`)
+    #[clap(
+        long = "web.listen-address",
+        env = "WEB_LISTEN_ADDRESS",
+        value_delimiter = ',',
+        default_value = "0.0.0.0:8080"
+    )]
+    listen: Vec<SocketAddr>,
+    /// Path under which the metrics are exposed
+    #[clap(
+        long = "web.telemetry-path",
+        env = "WEB_TELEMETRY_PATH",
+        default_value = "/"
+    )]
+    metrics_path: String,
+    /// Web configuration file enabling TLS and authentication, in the
+    /// format of the Prometheus exporter-toolkit
+    #[clap(long = "web.config.file", env = "WEB_CONFIG_FILE")]
+    web_config: Option<PathBuf>,
+    /// Seconds since the last successful contact with the inverter after
+    /// which /ready reports the exporter as not ready
+    #[clap(long, env, default_value_t = 60)]
+    ready_max_age_seconds: u64,
+    /// Timeout of the quick ping deciding whether the inverter is up
+    #[clap(long, env, default_value_t = 1000)]
+    ping_timeout_ms: u64,
+}
+
+/// Write options given on the command line. Only interactive commands ask
+/// for confirmation. The audit log file is created with the first write, so
+/// commands that only read don't leave one behind.
+fn write_options(cli: &Cli, interactive: bool) -> WriteOptions {
+    let audit_log = if cli.dry_run {
+        None
+    } else {
+        Some(Arc::new(AuditLog::new(&cli.audit_log)))
+    };
+    let confirm: Option<Confirmation> = if interactive && !cli.yes {
+        Some(Arc::new(commands::confirm_changes))
+    } else {
+        None
+    };
+
+    WriteOptions {
+        dry_run: cli.dry_run,
+        confirm,
+        audit_log,
+    }
+}
+
+/// Run a command that changes settings with the write options given on the
+/// command line
+fn with_write_options(cli: &Cli, command: impl FnOnce(&WriteOptions) -> ExitCode) -> ExitCode {
+    command(&write_options(cli, true))
+}
+
+/// Record or replay the frames exchanged with the inverter as asked for on
+/// the command line
+fn transport_options(cli: &Cli) -> Result<TransportOptions, String> {
+    if let Some(path) = &cli.record {
+        return TransportOptions::record_to(path)
+            .map_err(|e| format!("Unable to open recording {}: {e}", path.display()));
+    }
+    if let Some(path) = &cli.replay {
+        return TransportOptions::replay_from(path).map_err(|e| e.to_string());
+    }
+    Ok(TransportOptions::default())
+}
+
+/// Load the derived metrics, rejecting references to unknown metrics right
+/// away instead of on every scrape
+fn derived_metrics(cli: &Cli) -> Result<DerivedMetrics, String> {
+    let Some(path) = &cli.derived_metrics else {
+        return Ok(DerivedMetrics::default());
+    };
+
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {e}", path.display()))?;
+    let derived: DerivedMetrics =
+        toml::from_str(&content).map_err(|e| format!("Unable to parse {}: {e}", path.display()))?;
+    derived
+        .validate(&metrics::et::all_metrics())
+        .map_err(|e| format!("Invalid derived metrics in {}: {e}", path.display()))?;
+    Ok(derived)
+}
+
+fn discover(address: &str, transport: &TransportOptions) -> ExitCode {
+    println!("Trying to discover GoodWe inverters...");
+    match GoodWeClient::discover_with(address, transport) {
+        Ok(inverters) => {
+            let mut found_inverters = 0;
+            for (addr, response) in inverters {
+                match response {
+                    Ok(response) => {
+                        found_inverters += 1;
+                        println!("{}: Discovered inverter at {}:", found_inverters, addr.ip());
+                        println!("\t- IP Address: {}", response.ip_address);
+                        println!("\t- Serial Number: {}", response.serial_number);
+                        println!("\t- WiFi Name: {}", response.wifi_name);
+                    }
+                    Err(e) => println!("Ignoring invalid response from {}: {e}", addr.ip()),
+                }
+            }
+
+            if found_inverters == 0 {
+                println!("Could not find any inverters");
+            } else {
+                println!("\nFound {} inverters", found_inverters);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            println!("Error while discovering inverters: {e}");
+            ExitCode::FAILURE
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> ExitCode {
     let cli = Cli::parse();
-    if let Some(target) = cli.target {
+    let transport = match transport_options(&cli) {
+        Ok(transport) => transport,
+        Err(e) => {
+            println!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // Neither comparing dumps nor discovery need a known inverter
+    match &cli.command {
+        Commands::Diff(args) => return commands::scan::run_diff(args),
+        Commands::Discover { address } => return discover(address, &transport),
+        _ => (),
+    }
+    let derived = match derived_metrics(&cli) {
+        Ok(derived) => derived,
+        Err(e) => {
+            println!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(target) = cli.target.clone() {
         match &cli.command {
-            Commands::Discover => {
-                let _ = discovery::discover_inverters();
-                ExitCode::SUCCESS
-            }
-            Commands::Identify => match identify::query_id(&target) {
-                Ok(id) => {
-                    println!("Inverter Identification");
-                    println!(" - Serial Number: {}", id.serial_number);
-                    println!(" - Firmware: {}", id.firmware);
-                    ExitCode::SUCCESS
-                }
-                Err(e) => {
-                    println!("Error while identifying inverter: {e}");
-                    ExitCode::FAILURE
+            Commands::Discover { address } => discover(address, &transport),
+            Commands::Identify => {
+                match identify::query_id(&target, &transport, Duration::from_secs(3)) {
+                    Ok(id) => {
+                        println!("Inverter Identification");
+                        println!(" - Serial Number: {}", id.serial_number);
+                        println!(" - Firmware: {}", id.firmware);
+                        ExitCode::SUCCESS
+                    }
+                    Err(e) => {
+                        println!("Error while identifying inverter: {e}");
+                        ExitCode::FAILURE
+                    }
                 }
-            },
+            }
             Commands::Metrics => {
-                let metric_sets = vec![
-                    metrics::et::base_metrics(),
-                    metrics::et::battery_metrics(),
-                    metrics::et::meter_metrics(),
-                ];
-                for mut metric_set in metric_sets {
-                    match metrics::get_metrics(&target, &mut metric_set) {
-                        Ok(_) => {
-                            println!("{}", metric_set);
-                        }
+                let mut result = ExitCode::SUCCESS;
+                let mut metric_sets = metrics::et::all_metrics();
+                let mut read = Vec::new();
+                for metric_set in metric_sets.iter_mut() {
+                    match metrics::get_metrics(&target, &transport, metric_set) {
+                        Ok(_) => read.push(true),
                         Err(e) => {
                             println!("Error retrieving metrics: {e}");
+                            result = ExitCode::FAILURE;
+                            read.push(false);
                         }
                     }
                 }
-                ExitCode::SUCCESS
+                // Families can span several sets, they are rendered together
+                let read_sets: Vec<&MetricSet> = metric_sets
+                    .iter()
+                    .zip(&read)
+                    .filter_map(|(metric_set, read)| read.then_some(metric_set))
+                    .collect();
+                if !read_sets.is_empty() {
+                    println!("{}", Exposition(read_sets));
+                }
+                if let Some(flow) = EnergyFlow::from_metric_sets(&metric_sets) {
+                    println!("{}", flow);
+                }
+                if !derived.metrics.is_empty() {
+                    println!("{}", derived.evaluate(&metric_sets));
+                }
+                result
             }
-            Commands::Prometheus => {
-                use axum::{routing::get, Router};
-
-                let app = Router::new().route("/", get(|| async { all_metrics(target).await }));
+            Commands::Prometheus(args) => {
+                // The control API would answer as if settings were changed
+                if cli.dry_run {
+                    println!("--dry-run can't be used with the prometheus command");
+                    return ExitCode::FAILURE;
+                }
+                let web_config = match &args.web_config {
+                    Some(path) => match server::webconfig::WebConfig::load(path) {
+                        Ok(web_config) => web_config,
+                        Err(e) => {
+                            println!("{e}");
+                            return ExitCode::FAILURE;
+                        }
+                    },
+                    None => server::webconfig::WebConfig::default(),
+                };
+                // The audit log is only needed if settings can be changed
+                let write_options = if web_config.control_api_enabled() {
+                    write_options(&cli, false)
+                } else {
+                    WriteOptions::default()
+                };
+                let options = server::ServerOptions {
+                    listen: args.listen.clone(),
+                    metrics_path: args.metrics_path.clone(),
+                    web_config,
+                    ready_max_age: Duration::from_secs(args.ready_max_age_seconds),
+                    ping_timeout: Duration::from_millis(args.ping_timeout_ms),
+                    write_options,
+                    transport: transport.clone(),
+                    derived_metrics: derived,
+                };
 
-                let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await.unwrap();
-                axum::serve(listener, app).await.unwrap();
-                ExitCode::SUCCESS
+                match server::serve(target, options).await {
+                    Ok(_) => ExitCode::SUCCESS,
+                    Err(e) => {
+                        println!("Error while serving metrics: {e}");
+                        ExitCode::FAILURE
+                    }
+                }
             }
+            Commands::Mode { command } => with_write_options(&cli, |options| {
+                commands::mode::run(&target, &transport, options, command)
+            }),
+            Commands::Battery { command } => with_write_options(&cli, |options| {
+                commands::battery::run(&target, &transport, options, command)
+            }),
+            Commands::ExportLimit { command } => with_write_options(&cli, |options| {
+                commands::export_limit::run(&target, &transport, options, command)
+            }),
+            Commands::Read(args) => commands::read::run(&target, &transport, args),
+            Commands::Scan(args) => commands::scan::run_scan(&target, &transport, args),
+            Commands::Diff(args) => commands::scan::run_diff(args),
+            Commands::Schedule { command } => with_write_options(&cli, |options| {
+                commands::schedule::run(&target, &transport, options, command)
+            }),
         }
     } else {
         println!("Please provide a target either as a command line argument or in the TARGET environment variable!");
         ExitCode::FAILURE
     }
 }
-
-type ResponseWithCode = (StatusCode, String);
-type ResponseResult = Result<String, ResponseWithCode>;
-
-async fn all_metrics(target: String) -> ResponseResult {
-    use std::fmt::Write as _;
-
-    let mut response = String::new();
-
-    let metric_sets = vec![
-        metrics::et::base_metrics(),
-        metrics::et::battery_metrics(),
-        metrics::et::meter_metrics(),
-    ];
-    for mut metric_set in metric_sets {
-        match metrics::get_metrics(&target, &mut metric_set) {
-            Ok(_) => match write!(&mut response, "{}", metric_set) {
-                Ok(_) => (),
-                Err(e) => {
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("Failed to transform metrics: {e}"),
-                    ));
-                }
-            },
-            Err(e) => {
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Error retrieving metrics: {e}"),
-                ));
-            }
-        }
-    }
-    Ok(response)
-}
@@ -0,0 +1,240 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use goodwe::{
+    metrics::{connection::Connection, modbus::ExceptionCode, MetricsError},
+    settings::{
+        battery::{self, BatterySetting, BatterySettings},
+        work_mode::{self, WorkMode},
+        SettingsError,
+    },
+};
+
+use super::InverterState;
+
+/// Routes of the control API, relative to its base path
+pub fn routes() -> Router<Arc<InverterState>> {
+    Router::new()
+        .route(
+            "/inverters/:id/settings/work_mode",
+            get(get_work_mode).put(put_work_mode),
+        )
+        .route(
+            "/inverters/:id/settings/battery",
+            get(get_battery).put(put_battery),
+        )
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WorkModeBody {
+    work_mode: String,
+}
+
+/// Battery settings to change, settings that are left out stay as they are
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatteryChange {
+    depth_of_discharge_on_grid: Option<u16>,
+    depth_of_discharge_off_grid: Option<u16>,
+    max_charge_current: Option<f32>,
+    max_discharge_current: Option<f32>,
+    soc_reserve: Option<u16>,
+}
+
+impl BatteryChange {
+    fn settings(&self) -> Vec<BatterySetting> {
+        [
+            self.depth_of_discharge_on_grid
+                .map(BatterySetting::DepthOfDischargeOnGrid),
+            self.depth_of_discharge_off_grid
+                .map(BatterySetting::DepthOfDischargeOffGrid),
+            self.max_charge_current
+                .map(BatterySetting::MaxChargeCurrent),
+            self.max_discharge_current
+                .map(BatterySetting::MaxDischargeCurrent),
+            self.soc_reserve.map(BatterySetting::SocReserve),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+/// Error returned as JSON, `error` tells clients what went wrong without
+/// having to parse the message
+#[derive(Serialize)]
+pub struct ApiError {
+    #[serde(skip)]
+    status: StatusCode,
+    error: &'static str,
+    /// The Modbus exception the inverter answered with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exception: Option<&'static str>,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, error: &'static str, message: String) -> Self {
+        ApiError {
+            status,
+            error,
+            exception: None,
+            message,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self)).into_response()
+    }
+}
+
+impl From<SettingsError> for ApiError {
+    fn from(e: SettingsError) -> Self {
+        let message = e.to_string();
+        match e {
+            SettingsError::Refused(code) => ApiError {
+                status: match code {
+                    ExceptionCode::SlaveDeviceBusy => StatusCode::SERVICE_UNAVAILABLE,
+                    _ => StatusCode::UNPROCESSABLE_ENTITY,
+                },
+                error: "refused",
+                exception: Some(exception_name(code)),
+                message,
+            },
+            SettingsError::NotApplied { .. } => {
+                ApiError::new(StatusCode::BAD_GATEWAY, "not_applied", message)
+            }
+            SettingsError::UnknownValue { .. } => {
+                ApiError::new(StatusCode::BAD_GATEWAY, "unknown_value", message)
+            }
+            SettingsError::InvalidSchedule(_) | SettingsError::OutOfRange { .. } => {
+                ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_value", message)
+            }
+            SettingsError::NoBattery => ApiError::new(StatusCode::CONFLICT, "no_battery", message),
+            SettingsError::CommunicationError(MetricsError::WriteNotAllowed { .. }) => {
+                ApiError::new(StatusCode::FORBIDDEN, "write_not_allowed", message)
+            }
+            SettingsError::CommunicationError(MetricsError::NetworkError(_)) => {
+                ApiError::new(StatusCode::GATEWAY_TIMEOUT, "unreachable", message)
+            }
+            SettingsError::CommunicationError(_) => {
+                ApiError::new(StatusCode::BAD_GATEWAY, "communication_error", message)
+            }
+        }
+    }
+}
+
+fn exception_name(code: ExceptionCode) -> &'static str {
+    match code {
+        ExceptionCode::IllegalFunction => "illegal_function",
+        ExceptionCode::IllegalDataAddress => "illegal_data_address",
+        ExceptionCode::IllegalDataValue => "illegal_data_value",
+        ExceptionCode::SlaveDeviceFailure => "slave_device_failure",
+        ExceptionCode::Acknowledge => "acknowledge",
+        ExceptionCode::SlaveDeviceBusy => "slave_device_busy",
+        ExceptionCode::MemoryParityError => "memory_parity_error",
+        ExceptionCode::GatewayPathUnavailable => "gateway_path_unavailable",
+        ExceptionCode::GatewayTargetFailedToRespond => "gateway_target_failed_to_respond",
+        ExceptionCode::Unknown(_) => "unknown",
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// Talk to the inverter with `id`, waiting for scrapes and other changes
+/// that are in progress. The exchange blocks, so it runs on a thread of its
+/// own instead of stalling the runtime.
+async fn with_inverter<T: Send + 'static>(
+    state: Arc<InverterState>,
+    id: &str,
+    request: impl FnOnce(&Connection) -> Result<T, SettingsError> + Send + 'static,
+) -> ApiResult<T> {
+    // Only a single inverter is served for now, it is known by its address
+    if id != state.target {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "unknown_inverter",
+            format!("No inverter with id '{id}'"),
+        ));
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        let _exchange = state.exchange.lock().unwrap();
+        let conn = Connection::open_with(&state.target, &state.transport)?
+            .with_write_options(state.write_options.clone());
+        let result = request(&conn)?;
+        state.mark_contact();
+        Ok(result)
+    })
+    .await
+    .map_err(|e| {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            format!("Request failed: {e}"),
+        )
+    })?;
+
+    result.map(Json).map_err(|e: SettingsError| e.into())
+}
+
+async fn get_work_mode(
+    State(state): State<Arc<InverterState>>,
+    Path(id): Path<String>,
+) -> ApiResult<WorkModeBody> {
+    with_inverter(state, &id, |conn| {
+        Ok(WorkModeBody {
+            work_mode: work_mode::get_work_mode(conn)?.to_string(),
+        })
+    })
+    .await
+}
+
+async fn put_work_mode(
+    State(state): State<Arc<InverterState>>,
+    Path(id): Path<String>,
+    Json(body): Json<WorkModeBody>,
+) -> ApiResult<WorkModeBody> {
+    let mode: WorkMode = body
+        .work_mode
+        .parse()
+        .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_value", e))?;
+
+    with_inverter(state, &id, move |conn| {
+        work_mode::set_work_mode(conn, mode)?;
+        Ok(WorkModeBody {
+            work_mode: work_mode::get_work_mode(conn)?.to_string(),
+        })
+    })
+    .await
+}
+
+async fn get_battery(
+    State(state): State<Arc<InverterState>>,
+    Path(id): Path<String>,
+) -> ApiResult<BatterySettings> {
+    with_inverter(state, &id, battery::get_battery_settings).await
+}
+
+async fn put_battery(
+    State(state): State<Arc<InverterState>>,
+    Path(id): Path<String>,
+    Json(change): Json<BatteryChange>,
+) -> ApiResult<BatterySettings> {
+    with_inverter(state, &id, move |conn| {
+        battery::set_battery_settings(conn, &change.settings())?;
+        battery::get_battery_settings(conn)
+    })
+    .await
+}
@@ -0,0 +1,336 @@
+use std::{
+    fmt::Display,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use tokio::task::JoinSet;
+
+use goodwe::{
+    identify::RequestError,
+    metrics::{
+        self, connection::Connection, derived::DerivedMetrics, flow::EnergyFlow, Exposition,
+        MetricSet, MetricsError, METRIC_UP,
+    },
+    safety::WriteOptions,
+    transport::TransportOptions,
+};
+
+mod control;
+pub mod webconfig;
+
+use self::webconfig::WebConfig;
+
+pub struct ServerOptions {
+    pub listen: Vec<SocketAddr>,
+    pub metrics_path: String,
+    pub web_config: WebConfig,
+    pub ready_max_age: Duration,
+    pub ping_timeout: Duration,
+    /// Applied to the writes of the control API
+    pub write_options: WriteOptions,
+    pub transport: TransportOptions,
+    /// Computed after every scrape from the metrics read
+    pub derived_metrics: DerivedMetrics,
+}
+
+struct InverterState {
+    target: String,
+    ready_max_age: Duration,
+    ping_timeout: Duration,
+    last_contact: Mutex<Option<Instant>>,
+    /// Held while talking Modbus to the inverter, so that scrapes and
+    /// setting changes don't interleave their requests
+    exchange: Mutex<()>,
+    write_options: WriteOptions,
+    transport: TransportOptions,
+    /// Kept between scrapes, so registers the inverter doesn't support are
+    /// only probed once
+    metric_sets: Mutex<Vec<MetricSet>>,
+    derived_metrics: DerivedMetrics,
+}
+
+impl InverterState {
+    fn mark_contact(&self) {
+        *self.last_contact.lock().unwrap() = Some(Instant::now());
+    }
+
+    fn contacted_recently(&self) -> bool {
+        match *self.last_contact.lock().unwrap() {
+            Some(instant) => instant.elapsed() <= self.ready_max_age,
+            None => false,
+        }
+    }
+}
+
+pub enum ServerError {
+    /// The metrics path and why it can't be used
+    InvalidPath(String, &'static str),
+    TlsError(std::io::Error),
+    NetworkError(SocketAddr, std::io::Error),
+}
+
+impl Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerError::InvalidPath(path, reason) => {
+                write!(f, "Metrics path '{path}' {reason}")
+            }
+            ServerError::TlsError(e) => write!(f, "Unable to load TLS certificate or key: {e}"),
+            ServerError::NetworkError(addr, e) => write!(f, "Unable to serve on {addr}: {e}"),
+        }
+    }
+}
+
+pub async fn serve(target: String, options: ServerOptions) -> Result<(), ServerError> {
+    check_metrics_path(
+        &options.metrics_path,
+        options.web_config.control_api_enabled(),
+    )?;
+
+    let tls = match &options.web_config.tls_server_config {
+        Some(tls) => {
+            // Several crypto backends may be compiled in, make sure rustls
+            // knows which one to use. Failure means one is already installed.
+            let _ = rustls::crypto::ring::default_provider().install_default();
+            Some(
+                RustlsConfig::from_pem_file(&tls.cert_file, &tls.key_file)
+                    .await
+                    .map_err(ServerError::TlsError)?,
+            )
+        }
+        None => None,
+    };
+
+    let state = Arc::new(InverterState {
+        target,
+        ready_max_age: options.ready_max_age,
+        ping_timeout: options.ping_timeout,
+        last_contact: Mutex::new(None),
+        exchange: Mutex::new(()),
+        write_options: options.write_options,
+        transport: options.transport,
+        metric_sets: Mutex::new(metrics::et::all_metrics()),
+        derived_metrics: options.derived_metrics,
+    });
+
+    let web_config = Arc::new(options.web_config);
+    // Health endpoints are meant for orchestrators and load balancers, they
+    // don't reveal anything about the inverter and stay unauthenticated.
+    let mut app = Router::new()
+        .route(&options.metrics_path, get(all_metrics))
+        .layer(middleware::from_fn_with_state(
+            web_config.clone(),
+            authenticate,
+        ))
+        .route(HEALTH_PATH, get(|| async { "OK" }))
+        .route(READY_PATH, get(ready));
+
+    if web_config.control_api_enabled() {
+        app = app.nest(
+            CONTROL_API_PATH,
+            control::routes().layer(middleware::from_fn_with_state(
+                web_config,
+                authenticate_control,
+            )),
+        );
+    }
+    let app = app.with_state(state);
+
+    let mut servers = JoinSet::new();
+    for addr in options.listen {
+        let app = app.clone();
+        let tls = tls.clone();
+        servers.spawn(async move {
+            let result = match tls {
+                Some(tls) => {
+                    axum_server::bind_rustls(addr, tls)
+                        .serve(app.into_make_service())
+                        .await
+                }
+                None => axum_server::bind(addr).serve(app.into_make_service()).await,
+            };
+            result.map_err(|e| ServerError::NetworkError(addr, e))
+        });
+    }
+
+    // The listeners only ever return on errors, so bail out on the first one
+    while let Some(result) = servers.join_next().await {
+        if let Ok(Err(e)) = result {
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Paths served besides the metrics
+const HEALTH_PATH: &str = "/healthz";
+const READY_PATH: &str = "/ready";
+const CONTROL_API_PATH: &str = "/api/v1";
+
+/// The metrics can't share a path with the other endpoints, the router
+/// would refuse to start
+fn check_metrics_path(path: &str, control_api_enabled: bool) -> Result<(), ServerError> {
+    let reason = if !path.starts_with('/') {
+        "has to start with a '/'"
+    } else if path == HEALTH_PATH || path == READY_PATH {
+        "is taken by a health endpoint"
+    } else if control_api_enabled
+        && path
+            .strip_prefix(CONTROL_API_PATH)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    {
+        "is taken by the control API"
+    } else {
+        return Ok(());
+    };
+
+    Err(ServerError::InvalidPath(path.to_owned(), reason))
+}
+
+/// Run a credential check on a blocking thread, bcrypt would hold up the
+/// runtime otherwise
+async fn check_blocking(
+    web_config: Arc<WebConfig>,
+    headers: HeaderMap,
+    check: fn(&WebConfig, &HeaderMap) -> bool,
+) -> bool {
+    tokio::task::spawn_blocking(move || check(&web_config, &headers))
+        .await
+        .unwrap_or(false)
+}
+
+async fn authenticate(
+    State(web_config): State<Arc<WebConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers().clone();
+    if check_blocking(web_config, headers, WebConfig::is_authorized).await {
+        return next.run(request).await;
+    }
+
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Basic")],
+        "Unauthorized",
+    )
+        .into_response()
+}
+
+async fn authenticate_control(
+    State(web_config): State<Arc<WebConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers().clone();
+    if check_blocking(web_config, headers, WebConfig::is_control_authorized).await {
+        return next.run(request).await;
+    }
+
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        "Unauthorized",
+    )
+        .into_response()
+}
+
+async fn ready(State(state): State<Arc<InverterState>>) -> ResponseWithCode {
+    // Scrapes and control requests record their contacts, readiness never
+    // talks to the inverter itself
+    if state.contacted_recently() {
+        (StatusCode::OK, "Ready".to_string())
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "No recent contact with the inverter".to_string(),
+        )
+    }
+}
+
+type ResponseWithCode = (StatusCode, String);
+type ResponseResult = Result<String, ResponseWithCode>;
+
+async fn all_metrics(State(state): State<Arc<InverterState>>) -> ResponseResult {
+    // Talking to the inverter blocks, keep it away from the runtime threads
+    tokio::task::spawn_blocking(move || scrape(&state))
+        .await
+        .unwrap_or_else(|e| {
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Error retrieving metrics: {e}"),
+            ))
+        })
+}
+
+fn scrape(state: &InverterState) -> ResponseResult {
+    use std::fmt::Write as _;
+
+    let mut response = format!("# TYPE {METRIC_UP} gauge\n");
+    let unreachable = |mut response: String| {
+        response.push_str(&format!("{METRIC_UP} 0\n"));
+        Ok(response)
+    };
+
+    let _exchange = state.exchange.lock().unwrap();
+    let conn = match Connection::open_with(&state.target, &state.transport) {
+        Ok(conn) => conn,
+        Err(MetricsError::NetworkError(_)) => return unreachable(response),
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Error retrieving metrics: {e}"),
+            ))
+        }
+    };
+    // A quick identification tells whether the inverter is up, so an
+    // unreachable inverter only costs the ping timeout. Any answer will do.
+    if let Err(RequestError::NetworkError(_) | RequestError::NoResponse) =
+        conn.identify(state.ping_timeout)
+    {
+        return unreachable(response);
+    }
+    let mut metric_sets = state.metric_sets.lock().unwrap();
+    for metric_set in metric_sets.iter_mut() {
+        match metrics::read_metrics(&conn, metric_set) {
+            // The inverter went away during the scrape
+            Err(MetricsError::NetworkError(_)) => return unreachable(response),
+            Ok(_) => (),
+            Err(e) => {
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Error retrieving metrics: {e}"),
+                ));
+            }
+        }
+    }
+    response.push_str(&format!("{METRIC_UP} 1\n"));
+    if let Err(e) = write!(
+        &mut response,
+        "{}",
+        Exposition(metric_sets.iter().collect())
+    ) {
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to transform metrics: {e}"),
+        ));
+    }
+    if let Some(flow) = EnergyFlow::from_metric_sets(&metric_sets) {
+        response.push_str(&flow.to_string());
+    }
+    response.push_str(&state.derived_metrics.evaluate(&metric_sets).to_string());
+    state.mark_contact();
+    Ok(response)
+}
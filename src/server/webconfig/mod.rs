@@ -0,0 +1,246 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs,
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+use axum::http::{header, HeaderMap};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{de::IgnoredAny, Deserialize};
+
+/// Subset of the web configuration file understood by the Prometheus
+/// exporter-toolkit. Unknown keys are rejected instead of ignored, so that
+/// a setting we do not support (e.g. client certificate authentication)
+/// can't silently leave the exporter less protected than expected. Keys
+/// that only tune the server are accepted and ignored.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct WebConfig {
+    pub tls_server_config: Option<TlsServerConfig>,
+    /// HTTP/2 and response headers, ignored
+    #[serde(default, rename = "http_server_config")]
+    _http_server_config: Option<IgnoredAny>,
+    #[serde(default)]
+    pub basic_auth_users: HashMap<String, String>,
+    /// Not part of the exporter-toolkit format: bcrypt hashes of tokens that
+    /// are accepted in an `Authorization: Bearer` header.
+    #[serde(default)]
+    pub bearer_auth_tokens: Vec<String>,
+    /// Not part of the exporter-toolkit format: bcrypt hashes of the bearer
+    /// tokens accepted by the control API. Without any, the API is disabled.
+    #[serde(default)]
+    pub control_api_tokens: Vec<String>,
+    #[serde(skip)]
+    verified: VerifiedCache,
+    #[serde(skip)]
+    control_verified: VerifiedCache,
+    /// Unknown users are checked against it, so they take as long to reject
+    /// as a wrong password
+    #[serde(skip)]
+    dummy_hash: OnceLock<String>,
+}
+
+/// Successful checks kept at most, the cache starts over once it is full
+const VERIFIED_CACHE_SIZE: usize = 100;
+
+/// `Authorization` header values that passed a check, so that bcrypt only
+/// has to verify a credential once and not on every scrape
+#[derive(Default)]
+struct VerifiedCache(Mutex<HashSet<String>>);
+
+impl VerifiedCache {
+    fn check(&self, value: &str, verify: impl FnOnce() -> bool) -> bool {
+        if self.0.lock().unwrap().contains(value) {
+            return true;
+        }
+        if !verify() {
+            return false;
+        }
+
+        let mut verified = self.0.lock().unwrap();
+        if verified.len() >= VERIFIED_CACHE_SIZE {
+            verified.clear();
+        }
+        verified.insert(value.to_owned());
+        true
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsServerConfig {
+    pub cert_file: String,
+    pub key_file: String,
+    /// Protocol versions and ciphers, ignored in favour of the defaults of
+    /// the TLS library
+    #[serde(default, rename = "min_version")]
+    _min_version: Option<IgnoredAny>,
+    #[serde(default, rename = "max_version")]
+    _max_version: Option<IgnoredAny>,
+    #[serde(default, rename = "cipher_suites")]
+    _cipher_suites: Option<IgnoredAny>,
+    #[serde(default, rename = "prefer_server_cipher_suites")]
+    _prefer_server_cipher_suites: Option<IgnoredAny>,
+    #[serde(default, rename = "curve_preferences")]
+    _curve_preferences: Option<IgnoredAny>,
+}
+
+pub enum WebConfigError {
+    IoError(std::io::Error),
+    ParseError(serde_yaml::Error),
+}
+
+impl Display for WebConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebConfigError::IoError(e) => write!(f, "Unable to read web config: {e}"),
+            WebConfigError::ParseError(e) => write!(f, "Unable to parse web config: {e}"),
+        }
+    }
+}
+
+impl WebConfig {
+    pub fn load(path: &Path) -> Result<Self, WebConfigError> {
+        let content = fs::read_to_string(path).map_err(WebConfigError::IoError)?;
+        serde_yaml::from_str(&content).map_err(WebConfigError::ParseError)
+    }
+
+    pub fn requires_auth(&self) -> bool {
+        !self.basic_auth_users.is_empty() || !self.bearer_auth_tokens.is_empty()
+    }
+
+    /// Check the `Authorization` header of a request against the configured
+    /// users and tokens. Verifying a credential the first time takes the
+    /// full bcrypt cost, so this has to run on a blocking thread.
+    pub fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        if !self.requires_auth() {
+            return true;
+        }
+
+        let Some(value) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+        else {
+            return false;
+        };
+
+        self.verified.check(value, || {
+            if let Some(credentials) = value.strip_prefix("Basic ") {
+                self.check_basic(credentials)
+            } else if let Some(token) = value.strip_prefix("Bearer ") {
+                check_token(token, &self.bearer_auth_tokens)
+            } else {
+                false
+            }
+        })
+    }
+
+    pub fn control_api_enabled(&self) -> bool {
+        !self.control_api_tokens.is_empty()
+    }
+
+    /// Check the bearer token of a request to the control API. Unlike the
+    /// metrics, it is never accessible without a token. Like
+    /// `is_authorized`, this has to run on a blocking thread.
+    pub fn is_control_authorized(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|value| {
+                self.control_verified.check(value, || {
+                    value
+                        .strip_prefix("Bearer ")
+                        .is_some_and(|token| check_token(token, &self.control_api_tokens))
+                })
+            })
+    }
+
+    fn check_basic(&self, credentials: &str) -> bool {
+        let Ok(decoded) = STANDARD.decode(credentials) else {
+            return false;
+        };
+        let Ok(decoded) = String::from_utf8(decoded) else {
+            return false;
+        };
+        let Some((user, password)) = decoded.split_once(':') else {
+            return false;
+        };
+
+        match self.basic_auth_users.get(user) {
+            Some(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            None => {
+                let _ = bcrypt::verify(password, self.dummy_hash());
+                false
+            }
+        }
+    }
+
+    /// Hash of the same cost as the one of a configured user
+    fn dummy_hash(&self) -> &str {
+        self.dummy_hash.get_or_init(|| {
+            let cost = self
+                .basic_auth_users
+                .values()
+                .find_map(|hash| hash.split('$').nth(2)?.parse().ok())
+                .unwrap_or(bcrypt::DEFAULT_COST);
+            bcrypt::hash("", cost).unwrap_or_default()
+        })
+    }
+}
+
+fn check_token(token: &str, hashes: &[String]) -> bool {
+    hashes
+        .iter()
+        .any(|hash| bcrypt::verify(token, hash).unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_server_tuning_keys() {
+        let config: WebConfig = serde_yaml::from_str(
+            "tls_server_config:\n  cert_file: cert.pem\n  key_file: key.pem\n  min_version: TLS12\n\
+             http_server_config:\n  http2: false\n",
+        )
+        .unwrap();
+        assert_eq!(config.tls_server_config.unwrap().cert_file, "cert.pem");
+    }
+
+    #[test]
+    fn rejects_client_authentication() {
+        let config = serde_yaml::from_str::<WebConfig>(
+            "tls_server_config:\n  cert_file: cert.pem\n  key_file: key.pem\n  client_auth_type: RequireAndVerifyClientCert\n",
+        );
+        assert!(config.is_err());
+    }
+
+    fn basic(user: &str, password: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let credentials = STANDARD.encode(format!("{user}:{password}"));
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Basic {credentials}").parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn checks_basic_credentials() {
+        let config: WebConfig = serde_yaml::from_str(&format!(
+            "basic_auth_users:\n  prometheus: {}\n",
+            bcrypt::hash("secret", 4).unwrap()
+        ))
+        .unwrap();
+
+        assert!(config.is_authorized(&basic("prometheus", "secret")));
+        // Answered from the cache
+        assert!(config.is_authorized(&basic("prometheus", "secret")));
+        assert!(!config.is_authorized(&basic("prometheus", "wrong")));
+        assert!(!config.is_authorized(&basic("unknown", "secret")));
+        assert!(config.dummy_hash().starts_with("$2b$04$"));
+    }
+}
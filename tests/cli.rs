@@ -0,0 +1,309 @@
+mod common;
+
+use std::fs;
+
+use common::{run, start_gw20k_et, start_inverter, INVERTER};
+
+#[test]
+fn discover_lists_inverter() {
+    let inverter = start_inverter(INVERTER);
+    let address = inverter.discovery_addr.to_string();
+
+    let (success, output) = run(&["discover", "--address", &address]);
+    assert!(success);
+    assert!(output.contains(
+        "1: Discovered inverter at 127.0.0.1:\n\
+         \t- IP Address: 192.168.1.50\n\
+         \t- Serial Number: 5020KETU000W0001\n\
+         \t- WiFi Name: Solar-WiFi123456\n"
+    ));
+    assert!(output.ends_with("\nFound 1 inverters\n"));
+}
+
+#[test]
+fn identify_prints_serial_number_and_firmware() {
+    let inverter = start_inverter(INVERTER);
+    let target = inverter.inverter_addr.to_string();
+
+    let (success, output) = run(&["--target", &target, "identify"]);
+    assert!(success);
+    assert_eq!(
+        output,
+        "Inverter Identification\n - Serial Number: 5020KETU000W0001\n - Firmware: 04029-23-S\n"
+    );
+}
+
+#[test]
+fn identify_fails_without_answer() {
+    let inverter = start_inverter("[faults]\ndrop_rate = 1.0");
+    let target = inverter.inverter_addr.to_string();
+
+    let (success, output) = run(&["--target", &target, "identify"]);
+    assert!(!success);
+    assert_eq!(
+        output,
+        "Error while identifying inverter: No response received\n"
+    );
+}
+
+#[test]
+fn metrics_prints_exposition() {
+    let inverter = start_gw20k_et();
+    let target = inverter.inverter_addr.to_string();
+
+    let (success, output) = run(&["--target", &target, "metrics"]);
+    assert!(success);
+    assert_eq!(output, include_str!("expected/metrics.txt"));
+}
+
+#[test]
+fn metrics_reports_timeouts() {
+    let inverter = start_inverter("[faults]\ndrop_rate = 1.0");
+    let target = inverter.inverter_addr.to_string();
+
+    let (success, output) = run(&["--target", &target, "metrics"]);
+    assert!(!success);
+    assert_eq!(output.lines().count(), 4);
+    assert!(output
+        .lines()
+        .all(|line| line.starts_with("Error retrieving metrics: Network Error: ")));
+}
+
+#[test]
+fn metrics_reports_corrupted_frames() {
+    let inverter = start_inverter(&format!("{INVERTER}\n[faults]\nbad_crc_rate = 1.0"));
+    let target = inverter.inverter_addr.to_string();
+
+    let (success, output) = run(&["--target", &target, "metrics"]);
+    assert!(!success);
+    assert_eq!(
+        output,
+        "Error retrieving metrics: Modbus Error: CRC-16 checksum wrong, data was corrupted\n"
+            .repeat(4)
+    );
+}
+
+#[test]
+fn metrics_include_derived_metrics() {
+    let inverter = start_inverter(INVERTER);
+    let target = inverter.inverter_addr.to_string();
+    let config =
+        std::env::temp_dir().join(format!("goodwe-prom-derived-{}.toml", std::process::id()));
+    fs::write(
+        &config,
+        r#"
+        [[metric]]
+        name = "goodwe_battery_charge_ratio"
+        expression = 'goodwe_battery_state_ratio{type="State of Charge"} / 100'
+
+        [[metric]]
+        name = "goodwe_battery_current_limit_sum_amperes"
+        expression = '''
+            goodwe_battery_current_limit_amperes{type="Charge"}
+            + goodwe_battery_current_limit_amperes{type="Discharge"}
+        '''
+        "#,
+    )
+    .unwrap();
+
+    let derived = config.to_str().unwrap();
+    let (success, output) = run(&["--target", &target, "--derived-metrics", derived, "metrics"]);
+    fs::remove_file(&config).unwrap();
+    assert!(success);
+    assert!(output.ends_with(
+        "# TYPE goodwe_battery_charge_ratio gauge\n\
+         goodwe_battery_charge_ratio {} 0.64\n\
+         # TYPE goodwe_battery_current_limit_sum_amperes gauge\n\
+         goodwe_battery_current_limit_sum_amperes {} 100\n\n"
+    ));
+}
+
+#[test]
+fn metrics_reject_unknown_references() {
+    let config = std::env::temp_dir().join(format!(
+        "goodwe-prom-derived-unknown-{}.toml",
+        std::process::id()
+    ));
+    fs::write(
+        &config,
+        "[[metric]]\nname = \"goodwe_test\"\nexpression = 'goodwe_unknown * 2'\n",
+    )
+    .unwrap();
+
+    let derived = config.to_str().unwrap();
+    let (success, output) = run(&[
+        "--target",
+        "127.0.0.1:1",
+        "--derived-metrics",
+        derived,
+        "metrics",
+    ]);
+    fs::remove_file(&config).unwrap();
+    assert!(!success);
+    assert!(
+        output.ends_with("Derived metric goodwe_test refers to unknown metric goodwe_unknown\n")
+    );
+}
+
+#[test]
+fn dry_run_prints_planned_writes() {
+    let inverter = start_inverter(INVERTER);
+    let target = inverter.inverter_addr.to_string();
+
+    let (success, output) = run(&[
+        "--target",
+        &target,
+        "--dry-run",
+        "export-limit",
+        "set",
+        "4000",
+    ]);
+    assert!(success);
+    assert!(output.starts_with(
+        "Would write register 47509 (export limit enabled): 1 -> 1\n\
+         Would write register 47510 (export limit): 4600 -> 4000\n\
+         Would send frame: f7 10 b9 95 00 02 04 00 01 0f a0"
+    ));
+    assert!(output.ends_with("\nDry run, nothing was changed\n"));
+    assert!(!output.contains("Export limit set"));
+}
+
+#[test]
+fn writes_are_recorded_before_and_after_sending() {
+    let inverter = start_inverter(INVERTER);
+    let target = inverter.inverter_addr.to_string();
+    let audit_log =
+        std::env::temp_dir().join(format!("goodwe-prom-audit-{}.log", std::process::id()));
+    let _ = fs::remove_file(&audit_log);
+    let audit = audit_log.to_str().unwrap();
+
+    // Reading doesn't need the audit log, it isn't created
+    run(&[
+        "--target",
+        &target,
+        "--audit-log",
+        audit,
+        "export-limit",
+        "get",
+    ]);
+    assert!(!audit_log.exists());
+
+    let (success, _) = run(&[
+        "--target",
+        &target,
+        "--yes",
+        "--audit-log",
+        audit,
+        "export-limit",
+        "set",
+        "4000",
+    ]);
+    let content = fs::read_to_string(&audit_log).unwrap();
+    fs::remove_file(&audit_log).unwrap();
+    assert!(success);
+
+    let entries: Vec<&str> = content
+        .lines()
+        .map(|line| line.split_once(' ').unwrap().1)
+        .collect();
+    let entry = |register, old, new, status| {
+        format!("target={target} register={register} old={old} new={new} status={status}")
+    };
+    assert_eq!(
+        entries,
+        [
+            entry(47509, 1, 1, "sending"),
+            entry(47510, 4600, 4000, "sending"),
+            entry(47509, 1, 1, "applied"),
+            entry(47510, 4600, 4000, "applied"),
+        ]
+    );
+}
+
+#[test]
+fn prometheus_rejects_dry_run() {
+    let (success, output) = run(&["--target", "127.0.0.1:1", "--dry-run", "prometheus"]);
+    assert!(!success);
+    assert_eq!(
+        output,
+        "--dry-run can't be used with the prometheus command\n"
+    );
+}
+
+#[test]
+fn read_includes_the_last_register() {
+    let inverter = start_inverter("[[block]]\nstart = 65535\ncount = 1\n[registers]\n65535 = [42]");
+    let target = inverter.inverter_addr.to_string();
+
+    let (success, output) = run(&["--target", &target, "read", "--start", "65535"]);
+    assert!(success, "{output}");
+    assert!(output
+        .lines()
+        .nth(1)
+        .unwrap()
+        .starts_with("   65535 0x002a     42     42"));
+
+    let (success, output) = run(&[
+        "--target", &target, "read", "--start", "65535", "--count", "2",
+    ]);
+    assert!(!success);
+    assert_eq!(
+        output,
+        "Error: Registers to read exceed the address range\n"
+    );
+}
+
+#[test]
+fn scan_includes_the_last_register() {
+    let inverter = start_inverter("[[block]]\nstart = 65534\ncount = 2");
+    let target = inverter.inverter_addr.to_string();
+    let dump = std::env::temp_dir().join(format!("goodwe-prom-scan-{}.json", std::process::id()));
+
+    // The chunk fails on 65533, so every register up to 65535 is read on its own
+    let (success, output) = run(&[
+        "--target",
+        &target,
+        "scan",
+        "--start",
+        "65533",
+        "--count",
+        "3",
+        "--delay-ms",
+        "0",
+        "--output",
+        dump.to_str().unwrap(),
+    ]);
+    let _ = fs::remove_file(&dump);
+    assert!(success, "{output}");
+    assert!(output.contains("2 registers responded, 1 are illegal addresses, 0 failed\n"));
+
+    let (success, output) = run(&[
+        "--target", &target, "scan", "--start", "65533", "--count", "4",
+    ]);
+    assert!(!success);
+    assert_eq!(
+        output,
+        "Error: Registers to scan exceed the address range\n"
+    );
+}
+
+#[test]
+fn replay_answers_from_recording() {
+    let recording = std::env::temp_dir().join(format!(
+        "goodwe-prom-recording-{}.jsonl",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&recording);
+    let path = recording.to_str().unwrap();
+
+    let inverter = start_inverter(INVERTER);
+    let target = inverter.inverter_addr.to_string();
+    let (success, recorded) = run(&["--target", &target, "--record", path, "identify"]);
+    assert!(success);
+    drop(inverter);
+
+    let (success, replayed) = run(&["--target", &target, "--replay", path, "identify"]);
+    fs::remove_file(&recording).unwrap();
+    assert!(success);
+    assert_eq!(replayed, recorded);
+}
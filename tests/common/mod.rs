@@ -0,0 +1,127 @@
+#![allow(dead_code)]
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    process::{Child, Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use goodwe::simulator::{Simulator, SimulatorConfig, SimulatorHandle};
+
+/// Export limit and battery registers, the other metric sets are missing
+pub const INVERTER: &str = r#"
+    serial_number = "5020KETU000W0001"
+    firmware = "04029-23-S"
+    ip_address = "192.168.1.50"
+    wifi_name = "Solar-WiFi123456"
+
+    [[block]]
+    start = 37000
+    count = 24
+
+    [registers]
+    37000 = [1, 0, 2, 254, 50, 50, 0, 64, 99, 4, 256, 0, 0, 0, 22, 5, 3, 11, 7, 2, 262, 241, 3341, 3327]
+    47509 = [1, 4600]
+"#;
+
+/// Register image of a GW20K-ET the simulator uses by default, covering all
+/// metric sets
+pub const GW20K_ET: &str = include_str!("../../src/bin/goodwe-sim/gw20k-et.toml");
+
+/// Start a simulated inverter on ports chosen by the OS
+pub fn start_inverter(config: &str) -> SimulatorHandle {
+    spawn(SimulatorConfig::parse(config).unwrap_or_else(|e| panic!("{e}")))
+}
+
+/// Start a simulated GW20K-ET, without its script so the values don't
+/// change while a test runs
+pub fn start_gw20k_et() -> SimulatorHandle {
+    let mut config = SimulatorConfig::parse(GW20K_ET).unwrap_or_else(|e| panic!("{e}"));
+    config.script.clear();
+    spawn(config)
+}
+
+fn spawn(config: SimulatorConfig) -> SimulatorHandle {
+    Simulator::new(config)
+        .unwrap_or_else(|e| panic!("{e}"))
+        .spawn(
+            "127.0.0.1:0".parse().unwrap(),
+            "127.0.0.1:0".parse().unwrap(),
+        )
+        .unwrap()
+}
+
+fn command(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_goodwe-prom"));
+    command
+        .args(args)
+        .env_remove("TARGET")
+        .env_remove("AUDIT_LOG");
+    command
+}
+
+/// Run the exporter to completion, returning whether it succeeded and what
+/// it printed
+pub fn run(args: &[&str]) -> (bool, String) {
+    let Output { status, stdout, .. } = command(args).output().unwrap();
+    (status.success(), String::from_utf8(stdout).unwrap())
+}
+
+/// Exporter serving metrics, killed when dropped
+pub struct Server {
+    child: Child,
+    pub port: u16,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub fn start_server(target: &str, extra_args: &[&str]) -> Server {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let listen = format!("127.0.0.1:{port}");
+
+    let mut args = vec![
+        "--target",
+        target,
+        "prometheus",
+        "--web.listen-address",
+        &listen,
+    ];
+    args.extend_from_slice(extra_args);
+    let child = command(&args).stdout(Stdio::null()).spawn().unwrap();
+    let server = Server { child, port };
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while TcpStream::connect(&listen).is_err() {
+        assert!(Instant::now() < deadline, "server did not start");
+        thread::sleep(Duration::from_millis(20));
+    }
+    server
+}
+
+/// Send a GET request, returning status code and body
+pub fn get(server: &Server, path: &str, headers: &[(&str, &str)]) -> (u16, String) {
+    let mut stream = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+    let mut request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n");
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, body.to_owned())
+}